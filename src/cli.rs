@@ -0,0 +1,39 @@
+//! Helpers for command line programs using a [PasswordManager](crate::PasswordManager), such as the demo binary.
+
+use std::io::{self, BufRead, IsTerminal, Write};
+
+/// Print `prompt` and read the master password from the terminal without echoing it, so it can't be read over the user's shoulder.
+///
+/// When stdin isn't a terminal, such as when the password is piped in, there is nothing to echo so the password is read as a plain line with
+/// [read_master_password] instead.
+pub fn prompt_master_password(prompt: &str) -> io::Result<String> {
+    let stdin = io::stdin();
+    if stdin.is_terminal() {
+        return rpassword::prompt_password(prompt);
+    }
+    let mut stdout = io::stdout();
+    stdout.write_all(prompt.as_bytes())?;
+    stdout.flush()?;
+    read_master_password(stdin.lock())
+}
+
+/// Read the master password as a single line from `reader`, without its trailing `\n` or `\r\n`.
+///
+/// Only the line ending is removed, so spaces at either end are kept as part of the password.  Returns an [io::ErrorKind::UnexpectedEof] error if
+/// `reader` is already at its end rather than treating that as an empty password.
+pub fn read_master_password(mut reader: impl BufRead) -> io::Result<String> {
+    let mut password = String::new();
+    if reader.read_line(&mut password)? == 0 {
+        return Err(io::Error::new(
+            io::ErrorKind::UnexpectedEof,
+            "no master password was given",
+        ));
+    }
+    if password.ends_with('\n') {
+        password.pop();
+        if password.ends_with('\r') {
+            password.pop();
+        }
+    }
+    Ok(password)
+}
@@ -0,0 +1,127 @@
+//! Random password generation for accounts stored in a [PasswordManager](crate::PasswordManager).
+//!
+//! Like the rest of this crate, this is an *EXAMPLE*.  The random number generator is a small non-cryptographic one chosen to avoid pulling in a
+//! dependency, so the passwords it generates shouldn't be relied on anywhere real security is needed.
+
+use std::collections::hash_map::RandomState;
+use std::hash::BuildHasher;
+
+const LOWERCASE: &[u8] = b"abcdefghijklmnopqrstuvwxyz";
+const UPPERCASE: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ";
+const DIGITS: &[u8] = b"0123456789";
+const SYMBOLS: &[u8] = b"!@#$%^&*()-_=+[]{};:,.<>?/";
+
+/// Which kinds of character a generated password may contain.
+///
+/// Every kind is included by default.  Each kind that is included is guaranteed to appear at least once, as long as the password is long enough to
+/// fit one of each.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CharsetOptions {
+    /// Include lowercase letters.
+    pub lowercase: bool,
+    /// Include uppercase letters.
+    pub uppercase: bool,
+    /// Include digits.
+    pub digits: bool,
+    /// Include symbols such as `!` and `#`.
+    pub symbols: bool,
+    /// Seed the random number generator so the same password is generated every time, or `None` to pick a random seed.
+    pub seed: Option<u64>,
+}
+
+impl Default for CharsetOptions {
+    fn default() -> Self {
+        CharsetOptions {
+            lowercase: true,
+            uppercase: true,
+            digits: true,
+            symbols: true,
+            seed: None,
+        }
+    }
+}
+
+impl CharsetOptions {
+    /// Get the sets of characters that have been included.
+    fn charsets(&self) -> Vec<&'static [u8]> {
+        [
+            (self.lowercase, LOWERCASE),
+            (self.uppercase, UPPERCASE),
+            (self.digits, DIGITS),
+            (self.symbols, SYMBOLS),
+        ]
+        .into_iter()
+        .filter_map(|(included, charset)| included.then_some(charset))
+        .collect()
+    }
+}
+
+/// Generate a random password `len` characters long using the characters allowed by `opts`.
+///
+/// # Panics
+///
+/// Panics if `opts` doesn't include any kind of character.
+pub fn generate_password(len: usize, opts: CharsetOptions) -> String {
+    let charsets = opts.charsets();
+    assert!(
+        !charsets.is_empty(),
+        "At least one kind of character must be included"
+    );
+
+    let mut rng = SplitMix64::new(opts.seed.unwrap_or_else(random_seed));
+    let all: Vec<u8> = charsets.concat();
+
+    // Pick one character from each included set first so every kind is guaranteed to appear, then fill the rest from all of them.
+    let mut password: Vec<u8> = charsets
+        .iter()
+        .copied()
+        .chain(std::iter::repeat(all.as_slice()))
+        .take(len)
+        .map(|charset| rng.choose(charset))
+        .collect();
+
+    // Shuffle so the guaranteed characters aren't always at the start.
+    for i in (1..password.len()).rev() {
+        let j = rng.below(i + 1);
+        password.swap(i, j);
+    }
+
+    // Every character set is ASCII, so this can't fail.
+    String::from_utf8(password).expect("Generated passwords are always ASCII")
+}
+
+/// Pick a seed for when the caller didn't supply one.
+///
+/// This borrows the random keys the standard library generates for each [RandomState], the same as the master password salt does.
+fn random_seed() -> u64 {
+    RandomState::new().hash_one(())
+}
+
+/// A small seedable random number generator.
+///
+/// SplitMix64 is fast and simple, but it is not cryptographically secure.
+struct SplitMix64(u64);
+
+impl SplitMix64 {
+    fn new(seed: u64) -> Self {
+        SplitMix64(seed)
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    /// Get a random number from `0` up to but not including `bound`.  The slight bias from using the remainder doesn't matter for such small bounds.
+    fn below(&mut self, bound: usize) -> usize {
+        (self.next_u64() % bound as u64) as usize
+    }
+
+    /// Pick a random byte from a non-empty slice.
+    fn choose(&mut self, bytes: &[u8]) -> u8 {
+        bytes[self.below(bytes.len())]
+    }
+}
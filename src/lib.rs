@@ -1,7 +1,23 @@
 #![allow(unused)]
+// Failed state transitions hand the manager back by value in their Err variant.  That is the point of the API, so it isn't boxed even though the
+// manager is fairly large.
+#![allow(clippy::result_large_err)]
+#![cfg_attr(not(feature = "std"), no_std)]
 
+extern crate alloc;
+
+// Reading the master password without echoing it needs a terminal, so it is only included for command line programs.
+#[cfg(feature = "cli")]
+pub mod cli;
+// Generating passwords needs the standard library for a random seed.
+#[cfg(feature = "std")]
+pub mod generator;
 mod password_manager;
+// Sharing a manager between threads needs the standard library's `Mutex`.
+#[cfg(feature = "std")]
+pub mod shared;
 pub use password_manager::*;
 
-#[cfg(test)]
+// The tests use the standard library's clock and reference counting, so they are only run with it.
+#[cfg(all(test, feature = "std"))]
 mod tests;
@@ -1,4 +1,4 @@
-use rust_typestate::PasswordManagerBuilder;
+use rust_typestate::{FailedUnlock, PasswordManagerBuilder};
 
 /// Demonstration of the API in use.  Once again this is an EXAMPLE and not designed for real-world use.
 fn main() {
@@ -6,6 +6,7 @@ fn main() {
     // Try calling `.build()` without setting a master password.
     let mut manager = PasswordManagerBuilder::new()
         .with_master_password("Hunter2")
+        .with_max_attempts(3)
         .with_account("test@example.com", "Bees123")
         .with_account("person@social.com", "Wasps456")
         .with_account("me@news.biz", "Hornets789")
@@ -13,31 +14,26 @@ fn main() {
 
     // Below is a simple command line interface to show how this might be used.
 
-    // A simple loop to allow the user 3 attempts to enter their password correctly before exiting the program.
+    // A simple loop to allow the user 3 attempts to enter their password correctly before exiting the program.  The manager itself keeps track of the
+    // remaining attempts as it was built with `.with_max_attempts(3)`.
     //
     // This could be refactored into a function that returns a concrete type of [PasswordManager<Unlocked>] as the program is quit if the user does not
     // enter the correct password within the given guesses.
-    let mut remaining_attempts = 3;
     let unlocked_manager = loop {
-        if remaining_attempts == 0 {
-            println!("Too many incorrect password attempts!");
-            std::process::exit(0);
-        }
-        remaining_attempts -= 1;
-
-        // Get the user's password attempt.
-        let mut password_input = String::new();
-        println!("Enter the master password: ");
-        std::io::stdin()
-            .read_line(&mut password_input)
-            .expect("Failed to read line from stdin.");
+        // Get the user's password attempt.  With the `cli` feature it isn't echoed, so it can't be read over the user's shoulder.
+        let password_input = read_master_password();
 
-        match manager.unlock(password_input.trim()) {
+        match manager.unlock_or_lock_out(password_input) {
             // If the manager unlocks we break on it, returning the value from the loop.
             Ok(unlocked) => break unlocked,
-            // If the manager is still locked we have to replace the original variable with it as ".unlock()" consumes self.
+            // If the manager is still locked we have to replace the original variable with it as ".unlock_or_lock_out()" consumes self.
             // This is a weird quirk of the API but you can't conditionally choose between moving and taking a reference of self.
-            Err(still_locked) => manager = still_locked,
+            Err(FailedUnlock::Locked(still_locked)) => manager = still_locked,
+            // A locked out manager has no way to be unlocked again, so there's nothing left to do.
+            Err(FailedUnlock::LockedOut(_)) => {
+                println!("Too many incorrect password attempts!");
+                std::process::exit(0);
+            }
         }
     };
 
@@ -53,7 +49,8 @@ fn main() {
     println!("Getting password for account {account_input:?}...");
     let social_password = unlocked_manager.get_password(account_input);
     match social_password {
-        Some(ref password) => println!("Password is {password:?}."),
+        // Passwords print as `***` unless they are explicitly exposed.
+        Some(ref password) => println!("Password is {:?}.", password.expose()),
         None => println!("Looks like there isn't a password associated with that account."),
     }
 
@@ -62,3 +59,29 @@ fn main() {
     let _ = unlocked_manager.lock();
     println!("Password manager locked. Have a nice day.");
 }
+
+/// Prompt for the master password and read it from stdin without echoing it.
+#[cfg(feature = "cli")]
+fn read_master_password() -> String {
+    rust_typestate::cli::prompt_master_password("Enter the master password: ")
+        .expect("Failed to read the master password.")
+}
+
+/// Prompt for the master password and read it from stdin.  Without the `cli` feature it is echoed as it is typed.
+///
+/// Like with the `cli` feature, only the line ending is removed so spaces at either end are kept as part of the password.
+#[cfg(not(feature = "cli"))]
+fn read_master_password() -> String {
+    let mut password_input = String::new();
+    println!("Enter the master password: ");
+    std::io::stdin()
+        .read_line(&mut password_input)
+        .expect("Failed to read line from stdin.");
+    if password_input.ends_with('\n') {
+        password_input.pop();
+        if password_input.ends_with('\r') {
+            password_input.pop();
+        }
+    }
+    password_input
+}
@@ -3,126 +3,4096 @@
 
 // The PhantomData type allows us to add generic types to structs without actually using them in the struct.  It is a Zero-Sized type meaning it is
 // optimised away by the Rust compiler and only exists to benefit the developer.
+use alloc::borrow::{Cow, ToOwned};
+use alloc::string::{String, ToString};
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::any::Any;
+use core::borrow::Borrow;
+use core::cmp::Ordering;
+use core::fmt;
+use core::hash::{BuildHasher, Hash};
+use core::iter;
 use core::marker::PhantomData;
-use std::collections::HashMap;
+use core::mem;
+// Without the standard library, `hashbrown` provides the same `HashMap` that the standard library's is built on.
+#[cfg(not(feature = "std"))]
+use hashbrown::{DefaultHashBuilder, HashMap, HashSet};
+#[cfg(feature = "std")]
+use std::collections::hash_map::RandomState as DefaultHashBuilder;
+#[cfg(feature = "std")]
+use std::collections::{HashMap, HashSet};
+#[cfg(feature = "std")]
+use std::env::VarError;
+#[cfg(feature = "std")]
+use std::path::Path;
+#[cfg(feature = "std")]
+use std::time::{Duration, Instant};
+#[cfg(feature = "std")]
+use std::{fs, io};
+
+#[cfg(feature = "std")]
+use crate::generator::{generate_password, CharsetOptions};
 
 /// Denotes a locked [PasswordManager].
 #[derive(Debug)]
-pub struct Locked;
-/// Denotes an unlocked [PasswordManager].
+pub struct Locked;
+/// Denotes an unlocked [PasswordManager].
+///
+/// `S` tracks whether the manager has changes that haven't been saved yet.  It is [Saved] by default, which is what unlocking gives, and becomes
+/// [Dirty] once an account is inserted or removed.
+#[derive(Debug)]
+pub struct Unlocked<S = Saved>(PhantomData<S>);
+/// Denotes an [Unlocked] [PasswordManager] with no unsaved changes, so it can be locked without losing anything.
+#[derive(Debug)]
+pub struct Saved;
+/// Denotes an [Unlocked] [PasswordManager] with changes that haven't been saved.
+///
+/// [PasswordManager::insert] and [PasswordManager::remove_account] turn a [Saved] manager into a dirty one, and
+/// [PasswordManager::save_to_file] or [PasswordManager::mark_saved] turn it back.  A dirty manager can't be locked, so its changes can't be
+/// dropped by accident:
+///
+/// ```compile_fail,E0599
+/// use rust_typestate::{PasswordManager, Unlocked};
+///
+/// let manager = PasswordManager::<Unlocked>::from_accounts("Master Password", [("test@example.com", "Bees123")]);
+/// manager.insert("me@news.biz", "Hornets789").lock();
+/// ```
+#[derive(Debug)]
+pub struct Dirty;
+/// Denotes a [PasswordManager] that has been unlocked for reading only.  Its passwords can be read but not changed.
+///
+/// Methods that change a manager aren't implemented for this state at all, so trying to call them doesn't compile:
+///
+/// ```compile_fail,E0599
+/// use rust_typestate::PasswordManagerBuilder;
+///
+/// let mut manager = PasswordManagerBuilder::new()
+///     .with_master_password("Master Password")
+///     .build()
+///     .unlock_read_only("Master Password")
+///     .unwrap();
+/// manager.insert("test@example.com", "Bees123");
+/// ```
+///
+/// ```compile_fail,E0599
+/// use rust_typestate::PasswordManagerBuilder;
+///
+/// let mut manager = PasswordManagerBuilder::new()
+///     .with_master_password("Master Password")
+///     .with_account("test@example.com", "Bees123")
+///     .build()
+///     .unlock_read_only("Master Password")
+///     .unwrap();
+/// manager.remove_account("test@example.com");
+/// ```
+#[derive(Debug)]
+pub struct ReadOnly;
+/// Denotes a [PasswordManager] that ran out of unlock attempts.  It can never be unlocked again.
+#[derive(Debug)]
+pub struct LockedOut;
+/// Denotes a [PasswordManager] that hasn't had its master password chosen yet, such as during a first-run setup.
+///
+/// The only thing that can be done with it is setting the master password, which locks it.  It can't be unlocked or have accounts added before
+/// then:
+///
+/// ```compile_fail,E0599
+/// use rust_typestate::{NeedsSetup, PasswordManager};
+///
+/// let manager = PasswordManager::<NeedsSetup>::new();
+/// manager.unlock("Master Password");
+/// ```
+///
+/// ```compile_fail,E0599
+/// use rust_typestate::{NeedsSetup, PasswordManager};
+///
+/// let mut manager = PasswordManager::<NeedsSetup>::new();
+/// manager.insert("test@example.com", "Bees123");
+/// ```
+#[derive(Debug)]
+pub struct NeedsSetup;
+/// Denotes a [PasswordManager] that has been frozen as a snapshot, such as for archiving.  Its passwords can be read but never changed, and unlike
+/// [ReadOnly] it can't be locked or unlocked again, so there is no way back to a manager that can be changed:
+///
+/// ```compile_fail,E0599
+/// use rust_typestate::{PasswordManager, Unlocked};
+///
+/// let mut manager = PasswordManager::<Unlocked>::from_accounts("Master Password", [("test@example.com", "Bees123")]).freeze();
+/// manager.insert("test@example.com", "Wasps456");
+/// ```
+///
+/// ```compile_fail,E0599
+/// use rust_typestate::{PasswordManager, Unlocked};
+///
+/// let manager = PasswordManager::<Unlocked>::from_accounts("Master Password", [("test@example.com", "Bees123")]).freeze();
+/// manager.unlock("Master Password");
+/// ```
+#[derive(Debug)]
+pub struct Frozen;
+
+/// A state of [PasswordManager] whose accounts and passwords can be read, which is [Unlocked], [ReadOnly] and [Frozen].
+///
+/// This is sealed for the same reason as [ManagerState].
+pub trait Readable: sealed::Sealed {}
+impl<S: SaveState> Readable for Unlocked<S> {}
+impl Readable for ReadOnly {}
+impl Readable for Frozen {}
+
+/// A state a [PasswordManager] can be in, which is [Locked], [Unlocked], [ReadOnly], [LockedOut], [NeedsSetup] or [Frozen].
+///
+/// This is sealed so no other states can be added outside this crate.
+pub trait ManagerState: sealed::Sealed {}
+impl ManagerState for Locked {}
+impl<S: SaveState> ManagerState for Unlocked<S> {}
+impl ManagerState for ReadOnly {}
+impl ManagerState for LockedOut {}
+impl ManagerState for NeedsSetup {}
+impl ManagerState for Frozen {}
+
+/// Whether an [Unlocked] [PasswordManager] has unsaved changes, which is [Saved] or [Dirty].
+///
+/// This is sealed for the same reason as [ManagerState].
+pub trait SaveState: sealed::Sealed {}
+impl SaveState for Saved {}
+impl SaveState for Dirty {}
+
+mod sealed {
+    pub trait Sealed {}
+    impl Sealed for super::Locked {}
+    impl<S: super::SaveState> Sealed for super::Unlocked<S> {}
+    impl Sealed for super::Saved {}
+    impl Sealed for super::Dirty {}
+    impl Sealed for super::ReadOnly {}
+    impl Sealed for super::LockedOut {}
+    impl Sealed for super::NeedsSetup {}
+    impl Sealed for super::Frozen {}
+}
+
+/// A type that can be stored as a password in a [PasswordManager].
+///
+/// With the `zeroize` feature enabled this requires [zeroize::Zeroize] so stored passwords can be wiped from memory when the manager is dropped.
+/// Without it, this is implemented for every type.
+#[cfg(feature = "zeroize")]
+pub trait Wipe: zeroize::Zeroize {}
+#[cfg(feature = "zeroize")]
+impl<T: zeroize::Zeroize> Wipe for T {}
+
+/// A type that can be stored as a password in a [PasswordManager].
+///
+/// With the `zeroize` feature enabled this requires `zeroize::Zeroize` so stored passwords can be wiped from memory when the manager is dropped.
+/// Without it, this is implemented for every type.
+#[cfg(not(feature = "zeroize"))]
+pub trait Wipe {}
+#[cfg(not(feature = "zeroize"))]
+impl<T> Wipe for T {}
+
+/// A type that can be stored as an account or password in a [PasswordManager] that is locked or built.
+///
+/// With the `encryption` feature enabled this requires [serde::Serialize] and [serde::de::DeserializeOwned] so the accounts can be turned into bytes
+/// and encrypted while the manager is locked.  Locking panics if they fail to serialize.  Without it, this is implemented for every type.
+#[cfg(feature = "encryption")]
+pub trait Encrypt: serde::Serialize + serde::de::DeserializeOwned {}
+#[cfg(feature = "encryption")]
+impl<T: serde::Serialize + serde::de::DeserializeOwned> Encrypt for T {}
+
+/// A type that can be stored as an account or password in a [PasswordManager] that is locked or built.
+///
+/// With the `encryption` feature enabled this requires `serde::Serialize` and `serde::de::DeserializeOwned` so the accounts can be turned into bytes
+/// and encrypted while the manager is locked.  Locking panics if they fail to serialize.  Without it, this is implemented for every type.
+#[cfg(not(feature = "encryption"))]
+pub trait Encrypt {}
+#[cfg(not(feature = "encryption"))]
+impl<T> Encrypt for T {}
+
+/// A password that can't be accidentally printed.
+///
+/// Its [Debug](fmt::Debug) and [Display](fmt::Display) implementations print `***` instead of the password, so the only way to read it is to ask for
+/// it explicitly with [SecretString::expose].  This is the default password type of a [PasswordManager], and can be created from a [String] or
+/// `&str` so methods that take an `impl Into<..>` password can still be given plain strings.
+#[derive(Clone, Default, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(transparent)
+)]
+#[cfg_attr(feature = "zeroize", derive(zeroize::Zeroize))]
+pub struct SecretString(String);
+
+impl SecretString {
+    /// Get the password in plaintext.
+    pub fn expose(&self) -> &str {
+        &self.0
+    }
+
+    /// Get a mutable reference to the password in plaintext, so it can be edited in place.
+    pub fn expose_mut(&mut self) -> &mut String {
+        &mut self.0
+    }
+}
+
+impl From<String> for SecretString {
+    fn from(password: String) -> Self {
+        SecretString(password)
+    }
+}
+
+impl From<&str> for SecretString {
+    fn from(password: &str) -> Self {
+        SecretString(password.to_string())
+    }
+}
+
+// Comparing against another password or a plain string is constant time so checking a guess against a stored password doesn't leak how much of
+// it was right.
+impl PartialEq for SecretString {
+    fn eq(&self, other: &Self) -> bool {
+        constant_time_eq(self.0.as_bytes(), other.0.as_bytes())
+    }
+}
+
+impl PartialEq<str> for SecretString {
+    fn eq(&self, other: &str) -> bool {
+        constant_time_eq(self.0.as_bytes(), other.as_bytes())
+    }
+}
+
+impl fmt::Debug for SecretString {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "***")
+    }
+}
+
+impl fmt::Display for SecretString {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "***")
+    }
+}
+
+/// Everything a [PasswordManager] stores about an account.
+///
+/// An entry is created with just a password by methods like [PasswordManager::insert], and the rest can be filled in later.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(from = "EntryRepr<V>")
+)]
+pub struct AccountEntry<V = SecretString> {
+    /// The account's password.
+    pub password: V,
+    /// The address of the site the account is for.
+    pub url: Option<String>,
+    /// Any other information about the account.
+    pub notes: Option<String>,
+    /// Labels for grouping accounts, such as "work" or "banking".
+    pub tags: Vec<String>,
+    /// The passwords this account used to have, from oldest to most recent.
+    pub history: Vec<V>,
+    /// The account's name as it was given, if that isn't the key it is stored under, such as in a manager built with
+    /// [PasswordManagerBuilder::with_case_insensitive_accounts] where the key is lowercased.
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+    pub name: Option<String>,
+}
+
+impl<V> AccountEntry<V> {
+    /// Create an entry with a password and nothing else.
+    pub fn new(password: impl Into<V>) -> Self {
+        AccountEntry {
+            password: password.into(),
+            url: None,
+            notes: None,
+            tags: Vec::new(),
+            history: Vec::new(),
+            name: None,
+        }
+    }
+
+    /// Give the entry the name its account was given, if that isn't its key.
+    fn named(mut self, name: Option<String>) -> Self {
+        if name.is_some() {
+            self.name = name;
+        }
+        self
+    }
+
+    /// Get the name the account was given, which is `key` unless it is stored under a different key.
+    fn name_or<'a>(&'a self, key: &'a str) -> &'a str {
+        self.name.as_deref().unwrap_or(key)
+    }
+}
+
+#[cfg(feature = "zeroize")]
+impl<V: Wipe> AccountEntry<V> {
+    /// Overwrite the current and previous passwords with zeroes.
+    fn wipe(&mut self) {
+        use zeroize::Zeroize;
+
+        self.password.zeroize();
+        self.history.iter_mut().for_each(Zeroize::zeroize);
+    }
+}
+
+/// The accounts a [PasswordManager] holds, as kept by its [SecretStore].
+///
+/// This is the part of a store that works without looking accounts up, which is all a manager needs to count, wipe and lock its accounts.  It is
+/// implemented for [HashMap] and [BTreeMap](alloc::collections::BTreeMap) whatever their account types, so it never restricts which accounts a
+/// manager can hold.
+pub trait AccountList<K, V> {
+    /// Get the number of accounts stored.
+    fn len(&self) -> usize;
+
+    /// Check whether no accounts are stored.
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Get the number of accounts that can be stored without having to grow.  Stores that don't set aside room in advance report how many accounts
+    /// they hold.
+    fn capacity(&self) -> usize {
+        self.len()
+    }
+
+    /// Iterate over every account and everything stored about it.
+    fn iter<'a>(&'a self) -> impl Iterator<Item = (&'a K, &'a AccountEntry<V>)>
+    where
+        K: 'a,
+        V: 'a;
+
+    /// Iterate over every account with a mutable reference to everything stored about it, such as to wipe the passwords.
+    fn iter_mut<'a>(&'a mut self) -> impl Iterator<Item = (&'a K, &'a mut AccountEntry<V>)>
+    where
+        K: 'a,
+        V: 'a;
+
+    /// Remove every account, returning an iterator over them and everything stored about them.
+    fn drain(&mut self) -> impl Iterator<Item = (K, AccountEntry<V>)>;
+
+    /// Remove every account.
+    fn clear(&mut self);
+}
+
+/// Where a [PasswordManager] keeps its accounts.
+///
+/// A manager keeps its accounts in a [HashMap] by default.  This is also implemented for [BTreeMap](alloc::collections::BTreeMap), which keeps
+/// them sorted by account so they are always listed in the same order, and can be implemented for other backends such as one that keeps them
+/// encrypted.  An unlocked manager is moved into another store with [PasswordManager::with_store].
+///
+/// Accounts are looked up by any borrowed form of the account type that can be both hashed and ordered, so the same lookups work whichever store
+/// is used.
+pub trait SecretStore<K, V>: AccountList<K, V> {
+    /// Get everything stored about an account, or `None` if it isn't stored.
+    fn get<Q>(&self, account: &Q) -> Option<&AccountEntry<V>>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Ord + ?Sized;
+
+    /// Get a mutable reference to everything stored about an account, or `None` if it isn't stored.
+    fn get_mut<Q>(&mut self, account: &Q) -> Option<&mut AccountEntry<V>>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Ord + ?Sized;
+
+    /// Check whether an account is stored.
+    fn contains_key<Q>(&self, account: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Hash + Ord + ?Sized,
+    {
+        self.get(account).is_some()
+    }
+
+    /// Store an account, returning what was stored about it before if it already existed.
+    fn insert(&mut self, account: K, entry: AccountEntry<V>) -> Option<AccountEntry<V>>;
+
+    /// Get a mutable reference to everything stored about an account, first storing what `default` returns if the account doesn't exist.
+    fn get_or_insert_with<F: FnOnce() -> AccountEntry<V>>(
+        &mut self,
+        account: K,
+        default: F,
+    ) -> &mut AccountEntry<V>;
+
+    /// Remove an account, returning its name as it was stored along with everything stored about it if it existed.
+    fn remove_entry<Q>(&mut self, account: &Q) -> Option<(K, AccountEntry<V>)>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Ord + ?Sized;
+
+    /// Remove an account, returning everything stored about it if it existed.
+    fn remove<Q>(&mut self, account: &Q) -> Option<AccountEntry<V>>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Ord + ?Sized,
+    {
+        self.remove_entry(account).map(|(_, entry)| entry)
+    }
+
+    /// Keep only the accounts for which `f` returns `true`.
+    fn retain<F: FnMut(&K, &mut AccountEntry<V>) -> bool>(&mut self, f: F);
+
+    /// Make room for at least `additional` more accounts.  Stores that don't set aside room in advance do nothing.
+    fn reserve(&mut self, _additional: usize) {}
+
+    /// Free any room set aside for accounts that aren't stored.  Stores that don't set aside room in advance do nothing.
+    fn shrink_to_fit(&mut self) {}
+}
+
+impl<K, V, H> AccountList<K, V> for HashMap<K, AccountEntry<V>, H> {
+    fn len(&self) -> usize {
+        HashMap::len(self)
+    }
+
+    fn capacity(&self) -> usize {
+        HashMap::capacity(self)
+    }
+
+    fn iter<'a>(&'a self) -> impl Iterator<Item = (&'a K, &'a AccountEntry<V>)>
+    where
+        K: 'a,
+        V: 'a,
+    {
+        HashMap::iter(self)
+    }
+
+    fn iter_mut<'a>(&'a mut self) -> impl Iterator<Item = (&'a K, &'a mut AccountEntry<V>)>
+    where
+        K: 'a,
+        V: 'a,
+    {
+        HashMap::iter_mut(self)
+    }
+
+    fn drain(&mut self) -> impl Iterator<Item = (K, AccountEntry<V>)> {
+        HashMap::drain(self)
+    }
+
+    fn clear(&mut self) {
+        HashMap::clear(self);
+    }
+}
+
+impl<K: Eq + Hash, V, H: BuildHasher> SecretStore<K, V> for HashMap<K, AccountEntry<V>, H> {
+    fn get<Q>(&self, account: &Q) -> Option<&AccountEntry<V>>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Ord + ?Sized,
+    {
+        HashMap::get(self, account)
+    }
+
+    fn get_mut<Q>(&mut self, account: &Q) -> Option<&mut AccountEntry<V>>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Ord + ?Sized,
+    {
+        HashMap::get_mut(self, account)
+    }
+
+    fn insert(&mut self, account: K, entry: AccountEntry<V>) -> Option<AccountEntry<V>> {
+        HashMap::insert(self, account, entry)
+    }
+
+    fn get_or_insert_with<F: FnOnce() -> AccountEntry<V>>(
+        &mut self,
+        account: K,
+        default: F,
+    ) -> &mut AccountEntry<V> {
+        self.entry(account).or_insert_with(default)
+    }
+
+    fn remove_entry<Q>(&mut self, account: &Q) -> Option<(K, AccountEntry<V>)>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Ord + ?Sized,
+    {
+        HashMap::remove_entry(self, account)
+    }
+
+    fn retain<F: FnMut(&K, &mut AccountEntry<V>) -> bool>(&mut self, f: F) {
+        HashMap::retain(self, f);
+    }
+
+    fn reserve(&mut self, additional: usize) {
+        HashMap::reserve(self, additional);
+    }
+
+    fn shrink_to_fit(&mut self) {
+        HashMap::shrink_to_fit(self);
+    }
+}
+
+impl<K, V> AccountList<K, V> for alloc::collections::BTreeMap<K, AccountEntry<V>> {
+    fn len(&self) -> usize {
+        alloc::collections::BTreeMap::len(self)
+    }
+
+    fn iter<'a>(&'a self) -> impl Iterator<Item = (&'a K, &'a AccountEntry<V>)>
+    where
+        K: 'a,
+        V: 'a,
+    {
+        alloc::collections::BTreeMap::iter(self)
+    }
+
+    fn iter_mut<'a>(&'a mut self) -> impl Iterator<Item = (&'a K, &'a mut AccountEntry<V>)>
+    where
+        K: 'a,
+        V: 'a,
+    {
+        alloc::collections::BTreeMap::iter_mut(self)
+    }
+
+    fn drain(&mut self) -> impl Iterator<Item = (K, AccountEntry<V>)> {
+        mem::take(self).into_iter()
+    }
+
+    fn clear(&mut self) {
+        alloc::collections::BTreeMap::clear(self);
+    }
+}
+
+impl<K: Ord, V> SecretStore<K, V> for alloc::collections::BTreeMap<K, AccountEntry<V>> {
+    fn get<Q>(&self, account: &Q) -> Option<&AccountEntry<V>>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Ord + ?Sized,
+    {
+        alloc::collections::BTreeMap::get(self, account)
+    }
+
+    fn get_mut<Q>(&mut self, account: &Q) -> Option<&mut AccountEntry<V>>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Ord + ?Sized,
+    {
+        alloc::collections::BTreeMap::get_mut(self, account)
+    }
+
+    fn insert(&mut self, account: K, entry: AccountEntry<V>) -> Option<AccountEntry<V>> {
+        alloc::collections::BTreeMap::insert(self, account, entry)
+    }
+
+    fn get_or_insert_with<F: FnOnce() -> AccountEntry<V>>(
+        &mut self,
+        account: K,
+        default: F,
+    ) -> &mut AccountEntry<V> {
+        self.entry(account).or_insert_with(default)
+    }
+
+    fn remove_entry<Q>(&mut self, account: &Q) -> Option<(K, AccountEntry<V>)>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Ord + ?Sized,
+    {
+        alloc::collections::BTreeMap::remove_entry(self, account)
+    }
+
+    fn retain<F: FnMut(&K, &mut AccountEntry<V>) -> bool>(&mut self, f: F) {
+        alloc::collections::BTreeMap::retain(self, f);
+    }
+}
+
+/// Builds an [AccountEntry] for [PasswordManagerBuilder::with_account_using].
+///
+/// Like [PasswordManagerBuilder], the type tracks whether the password has been set.  It starts as an `AccountBuilder<MissingPassword>` and
+/// becomes an `AccountBuilder<V>` once [AccountBuilder::password] is called, so an account can't be added without a password.
+#[derive(Debug)]
+pub struct AccountBuilder<P = MissingPassword> {
+    password: P,
+    url: Option<String>,
+    notes: Option<String>,
+    tags: Vec<String>,
+}
+
+impl AccountBuilder {
+    fn new() -> Self {
+        AccountBuilder {
+            password: MissingPassword,
+            url: None,
+            notes: None,
+            tags: Vec::new(),
+        }
+    }
+
+    /// Set the account's password.
+    pub fn password<V>(self, password: impl Into<V>) -> AccountBuilder<V> {
+        AccountBuilder {
+            password: password.into(),
+            url: self.url,
+            notes: self.notes,
+            tags: self.tags,
+        }
+    }
+}
+
+impl<P> AccountBuilder<P> {
+    /// Set the address of the site the account is for.
+    pub fn url(self, url: impl Into<String>) -> Self {
+        Self {
+            url: Some(url.into()),
+            ..self
+        }
+    }
+
+    /// Set any other information about the account.
+    pub fn notes(self, notes: impl Into<String>) -> Self {
+        Self {
+            notes: Some(notes.into()),
+            ..self
+        }
+    }
+
+    /// Add a tag to the account if it doesn't already have it.
+    pub fn tag(mut self, tag: impl Into<String>) -> Self {
+        let tag = tag.into();
+        if !self.tags.contains(&tag) {
+            self.tags.push(tag);
+        }
+        self
+    }
+}
+
+impl<V> AccountBuilder<V> {
+    fn build(self) -> AccountEntry<V> {
+        AccountEntry {
+            password: self.password,
+            url: self.url,
+            notes: self.notes,
+            tags: self.tags,
+            history: Vec::new(),
+            name: None,
+        }
+    }
+}
+
+/// A single account in an unlocked [PasswordManager], which may or may not exist yet.
+///
+/// This is returned by [PasswordManager::entry] and works like the [Entry](std::collections::hash_map::Entry) of a [HashMap], so an account can be
+/// added or changed without checking whether it exists first.
+pub struct Entry<'a, K, V, H = DefaultHashBuilder> {
+    // The standard library's entries don't depend on the map's hasher but `hashbrown`'s do, so it is only kept in the type for the former.
+    #[cfg(feature = "std")]
+    entry: std::collections::hash_map::Entry<'a, K, AccountEntry<V>>,
+    #[cfg(not(feature = "std"))]
+    entry: hashbrown::hash_map::Entry<'a, K, AccountEntry<V>, H>,
+    // The name the account was given, kept in its entry if it is added.
+    name: Option<String>,
+    hasher: PhantomData<H>,
+}
+
+impl<'a, K: Eq + Hash, V, H: BuildHasher> Entry<'a, K, V, H> {
+    /// Add the account with `default` as its password if it doesn't exist, then get a mutable reference to its password.
+    pub fn or_insert(self, default: impl Into<V>) -> &'a mut V {
+        &mut self
+            .entry
+            .or_insert_with(|| AccountEntry::new(default).named(self.name))
+            .password
+    }
+
+    /// Add the account with the password returned by `default` if it doesn't exist, then get a mutable reference to its password.
+    pub fn or_insert_with<F: FnOnce() -> V>(self, default: F) -> &'a mut V {
+        &mut self
+            .entry
+            .or_insert_with(|| AccountEntry::new(default()).named(self.name))
+            .password
+    }
+
+    /// Change the account's password with `f` if the account exists.
+    ///
+    /// Like [PasswordManager::get_password_mut], the password is edited in place so the old one isn't added to the account's history.
+    pub fn and_modify<F: FnOnce(&mut V)>(self, f: F) -> Self {
+        Entry {
+            entry: self.entry.and_modify(|entry| f(&mut entry.password)),
+            name: self.name,
+            hasher: PhantomData,
+        }
+    }
+}
+
+/// The forms an [AccountEntry] can be deserialized from.  Managers serialized before entries existed stored each account's password on its own.
+#[cfg(feature = "serde")]
+#[derive(serde::Deserialize)]
+#[serde(untagged)]
+enum EntryRepr<V> {
+    Password(V),
+    Entry {
+        password: V,
+        #[serde(default)]
+        url: Option<String>,
+        #[serde(default)]
+        notes: Option<String>,
+        #[serde(default)]
+        tags: Vec<String>,
+        // A plain `#[serde(default)]` would require `V: Default` even though an empty `Vec` doesn't need it.
+        #[serde(default = "Vec::new")]
+        history: Vec<V>,
+        #[serde(default)]
+        name: Option<String>,
+    },
+}
+
+#[cfg(feature = "serde")]
+impl<V> From<EntryRepr<V>> for AccountEntry<V> {
+    fn from(repr: EntryRepr<V>) -> Self {
+        match repr {
+            EntryRepr::Password(password) => AccountEntry::new(password),
+            EntryRepr::Entry {
+                password,
+                url,
+                notes,
+                tags,
+                history,
+                name,
+            } => AccountEntry {
+                password,
+                url,
+                notes,
+                tags,
+                history,
+                name,
+            },
+        }
+    }
+}
+
+/// Something other than the master password itself that can unlock a [PasswordManager] with [PasswordManager::unlock_with].
+///
+/// Only available without the `hashing` feature, as a credential is checked against the plaintext master password.
+#[cfg(not(feature = "hashing"))]
+pub trait Credential {
+    /// Check whether this credential matches the manager's `stored` master password.  Implementations should compare in constant time.
+    fn matches(&self, stored: &str) -> bool;
+}
+
+/// A [Credential] for managers whose master password is a numeric PIN.
+///
+/// A PIN only matches a master password made up of exactly the same digits, so it can't unlock a manager with an ordinary master password.
+#[cfg(not(feature = "hashing"))]
+#[cfg_attr(feature = "zeroize", derive(zeroize::Zeroize, zeroize::ZeroizeOnDrop))]
+pub struct PinCredential(String);
+
+#[cfg(not(feature = "hashing"))]
+impl PinCredential {
+    /// Create a credential from a PIN.
+    pub fn new(pin: impl Into<String>) -> Self {
+        PinCredential(pin.into())
+    }
+}
+
+#[cfg(not(feature = "hashing"))]
+impl Credential for PinCredential {
+    fn matches(&self, stored: &str) -> bool {
+        let is_pin = !self.0.is_empty() && self.0.bytes().all(|byte| byte.is_ascii_digit());
+        is_pin && constant_time_eq(self.0.as_bytes(), stored.as_bytes())
+    }
+}
+
+/// The reason a [PasswordManager] failed to unlock.
+///
+/// This is marked as non-exhaustive so that new failure reasons can be added without breaking existing `match` statements.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum UnlockError {
+    /// The master password given did not match the one the manager was created with.
+    WrongPassword,
+    /// The manager has run out of unlock attempts, so it refuses to unlock even with the correct master password.
+    LockedOut,
+}
+
+impl fmt::Display for UnlockError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            UnlockError::WrongPassword => write!(f, "the master password is wrong"),
+            UnlockError::LockedOut => {
+                write!(f, "the password manager has run out of unlock attempts")
+            }
+        }
+    }
+}
+
+impl core::error::Error for UnlockError {}
+
+/// Turns an account name into the key it is stored and looked up under, along with the name to keep in its entry if that isn't the key itself.
+type NormalizeAccount<K> = fn(K) -> (K, Option<String>);
+
+/// Normalize an account name with a manager or builder's normalization function, if it has one.
+fn normalize<K>(normalize_account: Option<NormalizeAccount<K>>, account: K) -> (K, Option<String>) {
+    match normalize_account {
+        Some(normalize) => normalize(account),
+        None => (account, None),
+    }
+}
+
+/// The error returned when reading from a [PasswordManager] whose unlock timeout has passed.
+///
+/// The manager should be locked and unlocked again with [PasswordManager::unlock_with_timeout] to keep using it.
+#[cfg(feature = "std")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Expired;
+
+#[cfg(feature = "std")]
+impl fmt::Display for Expired {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "the password manager's unlock timeout has passed")
+    }
+}
+
+#[cfg(feature = "std")]
+impl core::error::Error for Expired {}
+
+/// The error returned by [PasswordManager::replace_password] and [PasswordManager::try_get_password] when the account doesn't exist.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AccountNotFound {
+    /// The name of the account that was asked for, such as to log which one was missing.
+    pub account: String,
+}
+
+impl AccountNotFound {
+    fn new(account: &str) -> Self {
+        AccountNotFound {
+            account: account.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for AccountNotFound {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "the account {:?} doesn't exist", self.account)
+    }
+}
+
+impl core::error::Error for AccountNotFound {}
+
+/// A moment in time, such as when an unlocked [PasswordManager] was unlocked or when it expires.
+#[cfg(feature = "std")]
+type Timestamp = Instant;
+
+/// A moment in time, such as when an unlocked [PasswordManager] was unlocked or when it expires.
+///
+/// Without the `std` feature there's no clock to read the time from, so this can never be created and managers never expire.
+#[cfg(not(feature = "std"))]
+enum Timestamp {}
+
+/// Get the current time, or `None` without the `std` feature as there's no clock to read it from.
+fn now() -> Option<Timestamp> {
+    #[cfg(feature = "std")]
+    return Some(Instant::now());
+    #[cfg(not(feature = "std"))]
+    return None;
+}
+
+/// The accounts that differ between two managers, as found by [PasswordManager::difference].
+///
+/// Each list is in no particular order.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VaultDiff<K = String> {
+    /// The accounts only stored in the manager `difference` was called on.
+    pub only_in_self: Vec<K>,
+    /// The accounts only stored in the manager it was compared with.
+    pub only_in_other: Vec<K>,
+    /// The accounts stored in both managers with different passwords.
+    pub changed: Vec<K>,
+}
+
+/// A change in the state of a [PasswordManager], passed to the hook registered with [PasswordManagerBuilder::on_transition].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum TransitionEvent {
+    /// The manager was locked.
+    Locked,
+    /// The manager was unlocked, including for reading only.
+    Unlocked,
+}
+
+/// A hook called when a [PasswordManager] changes state.  It is shared rather than boxed so locked managers can still be cloned, and has to be
+/// thread safe so managers can still be sent between threads.
+type TransitionHook = Arc<dyn Fn(TransitionEvent) + Send + Sync>;
+
+/// How [PasswordManager::merge] resolves an account that is stored in both managers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MergeStrategy {
+    /// Keep the password already stored in the manager being merged into.
+    KeepExisting,
+    /// Replace it with the password from the manager being merged in.
+    Overwrite,
+}
+
+/// The result of a failed call to [PasswordManager::unlock_or_lock_out].
+#[derive(Debug)]
+pub enum FailedUnlock<
+    K = String,
+    V: Wipe = SecretString,
+    H = DefaultHashBuilder,
+    B: AccountList<K, V> = HashMap<K, AccountEntry<V>, H>,
+> {
+    /// The master password was wrong but the manager still has attempts remaining.
+    Locked(PasswordManager<Locked, K, V, H, B>),
+    /// The master password was wrong and that was the manager's last attempt.
+    LockedOut(PasswordManager<LockedOut, K, V, H, B>),
+}
+
+/// The password manager struct.
+///
+/// Instead of embedding the locked state using a boolean field on the struct, it is implemented as a generic type.
+/// This has the benefit of there being no extra memory usage incurred by storing the state as a field within the struct itself, but the amount of memory
+/// taken by a single boolean value is negligable in most cases.
+///
+/// The main reason for this choice is to turn some runtime errors into compile time errors.  Instead of each method implemented for this struct having to
+/// check the manager's locked state and reacting to it (for example a .get_password(..) method having to return a [Result] in case the manager is locked),
+/// methods are only implemented on the struct for the states they apply to.  This simplifies the return type of the methods, reduces code duplication, and
+/// only exposes an API of methods that are valid to call given the current state.
+///
+/// The fields all being private also prevents accidentally leaking passwords from locked managers by just reading them.  The only safe way the API allows
+/// password retrieval is by getting them from an unlocked manager.
+///
+/// The account identifier type `K` and the stored password type `V` are also generic.  They default to a [HashMap<String, SecretString>] of account
+/// usernames to passwords, so a plain `PasswordManager` (or `PasswordManager<Unlocked>`) works the same as if the types were hardcoded.  The
+/// [BuildHasher] `H` used by that map can be swapped out with [PasswordManagerBuilder::with_hasher], such as for a faster or deterministic one.
+///
+/// The [SecretStore] `B` that keeps the accounts is that [HashMap] by default, and an unlocked manager can move them into another store with
+/// [PasswordManager::with_store], such as a [BTreeMap](alloc::collections::BTreeMap) to list them in order.
+///
+/// With the `zeroize` feature enabled, the master password and every stored password are overwritten with zeroes when the manager is dropped.  With
+/// the `encryption` feature enabled, a locked manager only holds its accounts in encrypted form.
+pub struct PasswordManager<
+    State = Locked,
+    K = String,
+    V: Wipe = SecretString,
+    H = DefaultHashBuilder,
+    B: AccountList<K, V> = HashMap<K, AccountEntry<V>, H>,
+> {
+    master_password: MasterKey,
+    password_list: B,
+    // The number of wrong master passwords that will be accepted before the manager locks out, or `None` if there is no limit.
+    attempts_remaining: Option<u8>,
+    // The number of attempts the manager is reset to after a successful unlock.
+    max_attempts: Option<u8>,
+    // The number of wrong master passwords given since the manager was built, loaded or last locked.
+    failed_attempts: u32,
+    // Turns an account name into the key it is stored and looked up under, or `None` to use account names as they are.  This is a function pointer
+    // rather than a flag as only `String` accounts know how to be made case-insensitive.  The name as it was given is kept in the account's entry.
+    normalize_account: Option<NormalizeAccount<K>>,
+    // Called whenever the manager is locked or unlocked, or `None` if nothing has been registered with [PasswordManagerBuilder::on_transition].
+    on_transition: Option<TransitionHook>,
+    // The moment an unlocked manager's timeout passes, or `None` if it doesn't expire.  Only the deadline is stored rather than the unlock time and
+    // timeout separately to keep the manager small, as it is moved around by value between states.
+    expires_at: Option<Timestamp>,
+    // The moment an unlocked manager was unlocked, or `None` if it isn't unlocked.
+    unlocked_at: Option<Timestamp>,
+    // The accounts of a locked manager, encrypted with a key derived from the master password, or `None` if they are stored in `password_list`.
+    sealed_accounts: Option<SealedAccounts>,
+    // The password type is only used through the store and the hasher only by the default store, but both are kept in the type so the default store
+    // can be named from them.
+    store_types: PhantomData<(V, H)>,
+    state: PhantomData<State>,
+}
+
+impl<K: Encrypt, V: Wipe + Encrypt, H, B: SecretStore<K, V> + Default>
+    PasswordManager<Locked, K, V, H, B>
+{
+    /// Attempt to unlock a password manager using the master password.
+    ///
+    /// Because the locked and unlocked managers are technically different types, this method has to return a
+    /// [Result<PasswordManager\<Unlocked>, PasswordManager\<Locked>>].  This has a few benefits:
+    /// - It forces the API user to handle the case of an invalid password being entered.
+    /// - Since this function moves the password manager, the Err variant gives back the original locked password manager in case of the wrong password.
+    ///
+    /// If the manager was built with a limited number of attempts, each wrong password uses one up.  Once they have all been used the manager refuses to
+    /// unlock even with the correct password.  Use [PasswordManager::unlock_or_lock_out] to have that reflected in the type of the returned manager.
+    pub fn unlock(
+        self,
+        master_password: impl Into<String>,
+    ) -> Result<PasswordManager<Unlocked, K, V, H, B>, Self> {
+        // Accepting an `impl Into<String>` is more flexible for the API caller than just `String` or `&str`.
+        let password = master_password.into();
+        self.unlock_if(|manager| match manager.master_password.matches(&password) {
+            true => manager.open_accounts(&password),
+            false => None,
+        })
+    }
+
+    /// Attempt to unlock a password manager using a [Credential] other than the master password itself, such as a PIN.
+    ///
+    /// This behaves the same as [PasswordManager::unlock], including using up an attempt if the credential doesn't match.  It isn't available with
+    /// the `hashing` feature as there's no plaintext master password left for the credential to be checked against.
+    #[cfg(not(feature = "hashing"))]
+    pub fn unlock_with<C: Credential>(
+        self,
+        credential: C,
+    ) -> Result<PasswordManager<Unlocked, K, V, H, B>, Self> {
+        self.unlock_if(
+            |manager| match credential.matches(&manager.master_password.0) {
+                // Without `hashing` there's no `encryption` either, so the accounts are never encrypted and don't need the master password to open.
+                true => manager.open_accounts(""),
+                false => None,
+            },
+        )
+    }
+
+    /// Unlock this manager if `open` gives back its accounts, which it should only do once the caller has proven they know the master password.
+    ///
+    /// This keeps track of the attempts remaining, refusing to call `open` at all once they have run out.
+    fn unlock_if(
+        mut self,
+        open: impl FnOnce(&mut Self) -> Option<B>,
+    ) -> Result<PasswordManager<Unlocked, K, V, H, B>, Self> {
+        if self.attempts_remaining == Some(0) {
+            self.failed_attempts = self.failed_attempts.saturating_add(1);
+            return Err(self);
+        }
+        match open(&mut self) {
+            Some(password_list) => {
+                let mut unlocked = self.transition::<Unlocked>();
+                unlocked.password_list = password_list;
+                unlocked.attempts_remaining = unlocked.max_attempts;
+                unlocked.failed_attempts = 0;
+                unlocked.sealed_accounts = None;
+                unlocked.unlocked_at = now();
+                unlocked.notify(TransitionEvent::Unlocked);
+                Ok(unlocked)
+            }
+            None => {
+                self.attempts_remaining = self.attempts_remaining.map(|remaining| remaining - 1);
+                self.failed_attempts = self.failed_attempts.saturating_add(1);
+                Err(self)
+            }
+        }
+    }
+
+    /// Attempt to unlock a password manager using the master password, reporting why it failed if it did.
+    ///
+    /// This behaves the same as [PasswordManager::unlock] but the Err variant also carries an [UnlockError] so the caller can both recover the
+    /// locked manager and branch on the reason it stayed locked.  A wrong password that uses up the manager's last attempt is reported as
+    /// [UnlockError::LockedOut].
+    #[allow(clippy::type_complexity)]
+    pub fn try_unlock(
+        self,
+        master_password: impl Into<String>,
+    ) -> Result<PasswordManager<Unlocked, K, V, H, B>, (Self, UnlockError)> {
+        self.unlock(master_password).map_err(|still_locked| {
+            let error = match still_locked.attempts_remaining {
+                Some(0) => UnlockError::LockedOut,
+                _ => UnlockError::WrongPassword,
+            };
+            (still_locked, error)
+        })
+    }
+
+    /// Attempt to unlock a password manager using the master password, moving it into the [LockedOut] state once it runs out of attempts.
+    ///
+    /// Unlike [PasswordManager::unlock], running out of attempts is enforced by the type system here.  A [PasswordManager\<LockedOut>] has no
+    /// `unlock` method, so there's no way to even try the master password again.
+    #[allow(clippy::type_complexity)]
+    pub fn unlock_or_lock_out(
+        self,
+        master_password: impl Into<String>,
+    ) -> Result<PasswordManager<Unlocked, K, V, H, B>, FailedUnlock<K, V, H, B>> {
+        self.unlock(master_password)
+            .map_err(|still_locked| match still_locked.attempts_remaining {
+                Some(0) => FailedUnlock::LockedOut(still_locked.transition()),
+                _ => FailedUnlock::Locked(still_locked),
+            })
+    }
+
+    /// Attempt to unlock a copy of this password manager using the master password, leaving this one locked where it is.
+    ///
+    /// This clones every stored password into the unlocked manager, but it means the caller doesn't have to take the locked manager back out of an
+    /// Err variant when the password is wrong.  It takes `&mut self` rather than `&self` so that wrong passwords still use up the manager's attempts.
+    pub fn unlock_ref(
+        &mut self,
+        master_password: &str,
+    ) -> Option<PasswordManager<Unlocked, K, V, H, B>>
+    where
+        B: Clone,
+    {
+        // Unlocking a copy reuses the checks and decryption of `unlock`, then only the attempts have to be kept in sync.
+        match self.clone().unlock(master_password) {
+            Ok(unlocked) => {
+                self.attempts_remaining = self.max_attempts;
+                self.failed_attempts = 0;
+                Some(unlocked)
+            }
+            Err(still_locked) => {
+                self.attempts_remaining = still_locked.attempts_remaining;
+                self.failed_attempts = still_locked.failed_attempts;
+                None
+            }
+        }
+    }
+
+    /// Attempt to unlock a password manager for reading only using the master password.
+    ///
+    /// This behaves the same as [PasswordManager::unlock], including using up an attempt on a wrong password, but the [ReadOnly] manager it gives
+    /// has no methods for adding, changing or removing accounts.  This makes it safe to hand to code that should only be able to look passwords up.
+    pub fn unlock_read_only(
+        self,
+        master_password: impl Into<String>,
+    ) -> Result<PasswordManager<ReadOnly, K, V, H, B>, Self> {
+        self.unlock(master_password)
+            .map(PasswordManager::transition)
+    }
+
+    /// Attempt to unlock a password manager using the master password, run `f` on the unlocked manager and then lock it again, returning the
+    /// locked manager along with what `f` returned.
+    ///
+    /// This is handy for a one-off read, as the unlocked manager never leaves `f` so it can't be forgotten about and left unlocked.  A wrong
+    /// password gives back the still locked manager without running `f`, using up an attempt the same as [PasswordManager::unlock].
+    pub fn verify_and_run<R>(
+        self,
+        master_password: &str,
+        f: impl FnOnce(&PasswordManager<Unlocked, K, V, H, B>) -> R,
+    ) -> Result<(Self, R), Self> {
+        let unlocked = self.unlock(master_password)?;
+        let result = f(&unlocked);
+        Ok((unlocked.lock(), result))
+    }
+
+    /// Attempt to unlock a password manager using the master password, giving an unlocked manager that expires after `timeout` has passed.
+    ///
+    /// Expiry can't be enforced by the type system as time passes at runtime, so an expired manager is still [Unlocked].  Instead,
+    /// [PasswordManager::get_password_checked] refuses to give out passwords once the manager has expired.
+    #[cfg(feature = "std")]
+    pub fn unlock_with_timeout(
+        self,
+        master_password: impl Into<String>,
+        timeout: Duration,
+    ) -> Result<PasswordManager<Unlocked, K, V, H, B>, Self> {
+        self.unlock(master_password).map(|mut unlocked| {
+            // A timeout too long to be represented as an `Instant` is treated as never expiring.
+            unlocked.expires_at = Instant::now().checked_add(timeout);
+            unlocked
+        })
+    }
+
+    /// Take the accounts out of this manager once its master password has been checked, decrypting them if they are encrypted.
+    #[cfg(not(feature = "encryption"))]
+    fn open_accounts(&mut self, _master_password: &str) -> Option<B> {
+        Some(mem::take(&mut self.password_list))
+    }
+
+    /// Take the accounts out of this manager once its master password has been checked, decrypting them if they are encrypted.
+    ///
+    /// Returns `None` if they fail to decrypt.  On success the key is kept with the master password so the accounts can be encrypted again when the
+    /// unlocked manager is locked.
+    #[cfg(feature = "encryption")]
+    fn open_accounts(&mut self, master_password: &str) -> Option<B> {
+        let key = EncryptionKey::derive(master_password, &self.master_password.salt);
+        let password_list = match &self.sealed_accounts {
+            Some(sealed_accounts) => sealed_accounts.open(&key)?,
+            // Accounts loaded from somewhere they weren't encrypted are only encrypted the next time the manager is locked.
+            None => mem::take(&mut self.password_list),
+        };
+        self.master_password.key = Some(key);
+        Some(password_list)
+    }
+}
+
+/// Attempt to unlock every manager in `managers` with the same master password, such as for several vaults that share one.
+///
+/// Returns the managers that unlocked followed by the ones that stayed locked, each in the order they were given.  Like
+/// [PasswordManager::unlock], every manager that stays locked uses up one of its attempts.
+pub fn unlock_all(
+    managers: Vec<PasswordManager<Locked>>,
+    master_password: &str,
+) -> (Vec<PasswordManager<Unlocked>>, Vec<PasswordManager<Locked>>) {
+    let mut unlocked = Vec::new();
+    let mut still_locked = Vec::new();
+    for manager in managers {
+        match manager.unlock(master_password) {
+            Ok(manager) => unlocked.push(manager),
+            Err(manager) => still_locked.push(manager),
+        }
+    }
+    (unlocked, still_locked)
+}
+
+// Functions that only report on a locked manager don't need to be able to unlock it, so they work for any account and password types.
+impl<K, V: Wipe, H, B: AccountList<K, V>> PasswordManager<Locked, K, V, H, B> {
+    /// Get the number of wrong master passwords this manager will accept before locking out, or [None] if there is no limit.
+    pub fn attempts_remaining(&self) -> Option<u8> {
+        self.attempts_remaining
+    }
+
+    /// Get the number of wrong master passwords this manager has been given since it was built, loaded or last locked.
+    ///
+    /// Unlike [PasswordManager::attempts_remaining] this is counted whether or not the manager has a limit, including attempts refused because it
+    /// had already run out.  Unlocking successfully starts the count again from zero.
+    pub fn failed_attempts(&self) -> u32 {
+        self.failed_attempts
+    }
+
+    /// Check whether `pw` is this manager's master password without unlocking it, such as for a "confirm password" dialog.
+    ///
+    /// The password is compared in constant time the same as by [PasswordManager::unlock], but the manager is left untouched so a wrong password
+    /// doesn't use up an attempt.  To keep this from getting around the limit, a manager that has run out of attempts never matches.
+    pub fn verify(&self, pw: &str) -> bool {
+        self.attempts_remaining != Some(0) && self.master_password.matches(pw)
+    }
+
+    /// Get the number of characters in the master password, such as to show the expected length as dots on a login screen.
+    ///
+    /// This counts characters rather than bytes, so a password with non-ASCII characters isn't reported as longer than it looks.  It isn't
+    /// available with the `hashing` feature as only a fixed length digest of the master password is kept.
+    #[cfg(not(feature = "hashing"))]
+    pub fn master_password_len(&self) -> usize {
+        self.master_password.0.chars().count()
+    }
+
+    /// Get the salted SHA-256 digest of the master password, such as to compare it against one stored elsewhere without unlocking the manager.
+    ///
+    /// The digest is of [PasswordManager::master_salt] followed by the master password's UTF-8 bytes.  Every manager gets its own random salt, so two
+    /// managers with the same master password only have the same digest if they also share a salt, such as a manager and its clone.
+    #[cfg(feature = "hashing")]
+    pub fn master_hash(&self) -> &[u8] {
+        &self.master_password.digest
+    }
+
+    /// Get the random salt the master password was hashed with for [PasswordManager::master_hash].
+    #[cfg(feature = "hashing")]
+    pub fn master_salt(&self) -> &[u8] {
+        &self.master_password.salt
+    }
+
+    /// Get the number of accounts stored in this manager without unlocking it.
+    ///
+    /// Only the count is revealed, not the account names or passwords, so it can be shown on a lock screen.
+    pub fn account_count(&self) -> usize {
+        self.count_accounts()
+    }
+}
+
+/// Cloning is only implemented for locked managers.  Both copies unlock with the same master password and can be changed independently afterwards.
+///
+/// An unlocked manager can't be cloned, so a live copy of every password can't be made by accident.  It has to be locked first:
+///
+/// ```compile_fail,E0599
+/// use rust_typestate::{PasswordManager, Unlocked};
+///
+/// let manager = PasswordManager::<Unlocked>::from_accounts("Master Password", [("test@example.com", "Bees123")]);
+/// let copy = manager.clone();
+/// ```
+impl<K, V: Wipe, H, B: AccountList<K, V> + Clone> Clone for PasswordManager<Locked, K, V, H, B> {
+    fn clone(&self) -> Self {
+        PasswordManager {
+            master_password: self.master_password.clone(),
+            password_list: self.password_list.clone(),
+            attempts_remaining: self.attempts_remaining,
+            max_attempts: self.max_attempts,
+            failed_attempts: self.failed_attempts,
+            normalize_account: self.normalize_account,
+            on_transition: self.on_transition.clone(),
+            expires_at: None,
+            unlocked_at: None,
+            sealed_accounts: self.sealed_accounts.clone(),
+            store_types: PhantomData,
+            state: PhantomData,
+        }
+    }
+}
+
+/// Two locked managers are equal if they have the same master password and accounts.  Settings such as the number of attempts aren't compared.
+///
+/// With the `hashing` feature the master passwords are compared by their salted digests, so only managers sharing a salt (such as clones) can be
+/// equal.  With the `encryption` feature the accounts are compared while they are encrypted, which is likewise only equal for clones.
+///
+/// Managers in different states can't be compared:
+///
+/// ```compile_fail,E0308
+/// use rust_typestate::{PasswordManager, Unlocked};
+///
+/// let unlocked = PasswordManager::<Unlocked>::from_accounts("Master Password", [("test@example.com", "Bees123")]);
+/// let locked = PasswordManager::<Unlocked>::from_accounts("Master Password", [("test@example.com", "Bees123")]).lock();
+/// let equal = locked == unlocked;
+/// ```
+impl<K, V: Wipe, H, B: AccountList<K, V> + PartialEq> PartialEq
+    for PasswordManager<Locked, K, V, H, B>
+{
+    fn eq(&self, other: &Self) -> bool {
+        self.master_password == other.master_password
+            && self.password_list == other.password_list
+            && self.sealed_accounts == other.sealed_accounts
+    }
+}
+
+/// Two unlocked managers are equal if they have the same master password and accounts.  Settings such as the number of attempts aren't compared.
+///
+/// With the `hashing` feature the master passwords are compared by their salted digests, so only managers sharing a salt can be equal.
+impl<S, K, V: Wipe, H, B: AccountList<K, V> + PartialEq> PartialEq
+    for PasswordManager<Unlocked<S>, K, V, H, B>
+{
+    fn eq(&self, other: &Self) -> bool {
+        self.master_password == other.master_password && self.password_list == other.password_list
+    }
+}
+
+// Serialization is only implemented for locked managers.  Deserializing always produces a locked manager, so secrets loaded from an untrusted source
+// still have to be unlocked with the master password before they can be read, and an unlocked manager can't be dumped without locking it first.
+#[cfg(feature = "serde")]
+impl<K, V, H, B> serde::Serialize for PasswordManager<Locked, K, V, H, B>
+where
+    V: Wipe,
+    B: AccountList<K, V> + serde::Serialize,
+{
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        use serde::ser::SerializeStruct;
+
+        // The state is skipped as it is always `Locked`.
+        let mut manager = serializer.serialize_struct("PasswordManager", 5)?;
+        manager.serialize_field("master_password", &self.master_password)?;
+        manager.serialize_field("password_list", &self.password_list)?;
+        manager.serialize_field("attempts_remaining", &self.attempts_remaining)?;
+        manager.serialize_field("max_attempts", &self.max_attempts)?;
+        match &self.sealed_accounts {
+            Some(sealed_accounts) => manager.serialize_field("sealed_accounts", sealed_accounts)?,
+            None => manager.skip_field("sealed_accounts")?,
+        }
+        manager.end()
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de, K, V, H, B> serde::Deserialize<'de> for PasswordManager<Locked, K, V, H, B>
+where
+    V: Wipe,
+    B: AccountList<K, V> + serde::Deserialize<'de>,
+{
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        // A mirror of the serialized fields without the state, which is filled in as `Locked` below regardless of what the input contained.
+        #[derive(serde::Deserialize)]
+        #[serde(rename = "PasswordManager")]
+        struct Fields<B> {
+            master_password: MasterKey,
+            password_list: B,
+            #[serde(default)]
+            attempts_remaining: Option<u8>,
+            #[serde(default)]
+            max_attempts: Option<u8>,
+            #[serde(default)]
+            sealed_accounts: Option<SealedAccounts>,
+        }
+
+        let fields = Fields::deserialize(deserializer)?;
+        Ok(PasswordManager {
+            master_password: fields.master_password,
+            password_list: fields.password_list,
+            attempts_remaining: fields.attempts_remaining,
+            max_attempts: fields.max_attempts,
+            failed_attempts: 0,
+            expires_at: None,
+            unlocked_at: None,
+            normalize_account: None,
+            on_transition: None,
+            sealed_accounts: fields.sealed_accounts,
+            store_types: PhantomData,
+            state: PhantomData,
+        })
+    }
+}
+
+impl PasswordManager<Locked> {
+    /// Start building a password manager, the same as [PasswordManagerBuilder::new].
+    ///
+    /// ```
+    /// use rust_typestate::PasswordManager;
+    ///
+    /// let manager = PasswordManager::builder()
+    ///     .with_master_password("Master Password")
+    ///     .with_account("test@example.com", "Bees123")
+    ///     .build();
+    /// assert_eq!(manager.account_count(), 1);
+    /// ```
+    pub fn builder() -> PasswordManagerBuilder<MissingPassword> {
+        PasswordManagerBuilder::new()
+    }
+}
+
+/// The bytes every file written by [PasswordManager::save_to_file] starts with.
+#[cfg(feature = "std")]
+const FILE_MAGIC: &[u8; 4] = b"RTPM";
+/// The version of the file format written by [PasswordManager::save_to_file].
+#[cfg(feature = "std")]
+const FILE_VERSION: u8 = 1;
+
+// Saving and loading files uses the default types so the format doesn't have to describe arbitrary account and password types.  Like serialization,
+// it is only implemented for locked managers and loading always produces a locked manager.
+#[cfg(feature = "std")]
+impl PasswordManager<Locked> {
+    /// Save this password manager to the file at `path`, replacing the file if it already exists.
+    ///
+    /// The file is *NOT* encrypted.  Every account and password is written as plaintext, and so is the master password unless the `hashing`
+    /// feature is enabled.  The format is a simple binary one where lengths are little-endian `u32`s, strings are their length followed by their
+    /// UTF-8 bytes, lists are their length followed by their items, and optional values are a `0` byte if missing or a `1` byte followed by the
+    /// value.  The file contains, in order:
+    ///
+    /// 1. The bytes `RTPM` followed by a format version byte, currently `1`.
+    /// 2. A `0` byte followed by the plaintext master password, or with `hashing` a `1` byte followed by its 16 byte salt and 32 byte digest.
+    /// 3. The optional number of unlock attempts remaining and optional maximum number of attempts, as single bytes.
+    /// 4. The list of accounts, each of which is its name, password, optional URL, optional notes, list of tags and list of previous passwords.
+    /// 5. The optional encrypted accounts from the `encryption` feature, as a 12 byte nonce, the number of accounts and the encrypted bytes.  The
+    ///    list of accounts before this is empty when they are encrypted.
+    pub fn save_to_file(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let mut writer = FileWriter(Vec::new());
+        writer.0.extend_from_slice(FILE_MAGIC);
+        writer.0.push(FILE_VERSION);
+        self.master_password.write_to(&mut writer)?;
+        writer.optional_u8(self.attempts_remaining);
+        writer.optional_u8(self.max_attempts);
+
+        writer.len(self.password_list.len())?;
+        for (account, entry) in &self.password_list {
+            writer.str(entry.name_or(account))?;
+            writer.str(entry.password.expose())?;
+            writer.optional_str(entry.url.as_deref())?;
+            writer.optional_str(entry.notes.as_deref())?;
+            writer.len(entry.tags.len())?;
+            for tag in &entry.tags {
+                writer.str(tag)?;
+            }
+            writer.len(entry.history.len())?;
+            for password in &entry.history {
+                writer.str(password.expose())?;
+            }
+        }
+        match &self.sealed_accounts {
+            Some(sealed_accounts) => {
+                writer.0.push(1);
+                sealed_accounts.write_to(&mut writer)?;
+            }
+            None => writer.0.push(0),
+        }
+
+        let result = fs::write(path, &writer.0);
+        #[cfg(feature = "zeroize")]
+        zeroize::Zeroize::zeroize(&mut writer.0);
+        result
+    }
+
+    /// Load a password manager from a file written by [PasswordManager::save_to_file].
+    ///
+    /// The loaded manager is always locked, so it has to be unlocked with the master password it was saved with before its passwords can be read.
+    /// A file that isn't in the expected format gives an error of kind [io::ErrorKind::InvalidData], as does a file saved with a different
+    /// setting of the `hashing` feature.
+    pub fn load_from_file(path: impl AsRef<Path>) -> io::Result<PasswordManager<Locked>> {
+        let mut bytes = fs::read(path)?;
+        let result = Self::read_file(&mut FileReader(&bytes));
+        #[cfg(feature = "zeroize")]
+        zeroize::Zeroize::zeroize(&mut bytes);
+        result
+    }
+
+    fn read_file(reader: &mut FileReader) -> io::Result<PasswordManager<Locked>> {
+        if reader.bytes(FILE_MAGIC.len())? != FILE_MAGIC {
+            return Err(invalid_data("not a password manager file"));
+        }
+        if reader.u8()? != FILE_VERSION {
+            return Err(invalid_data("unsupported password manager file version"));
+        }
+
+        let master_password = MasterKey::read_from(reader)?;
+        let attempts_remaining = reader.optional_u8()?;
+        let max_attempts = reader.optional_u8()?;
+
+        let mut password_list = HashMap::new();
+        for _ in 0..reader.len()? {
+            let account = reader.string()?;
+            let mut entry = AccountEntry::new(reader.string()?);
+            entry.url = reader.optional_string()?;
+            entry.notes = reader.optional_string()?;
+            for _ in 0..reader.len()? {
+                entry.tags.push(reader.string()?);
+            }
+            for _ in 0..reader.len()? {
+                entry.history.push(reader.string()?.into());
+            }
+            password_list.insert(account, entry);
+        }
+        let sealed_accounts = match reader.present()? {
+            true => Some(SealedAccounts::read_from(reader)?),
+            false => None,
+        };
+
+        if !reader.0.is_empty() {
+            return Err(invalid_data("unexpected data after the last account"));
+        }
+
+        Ok(PasswordManager {
+            master_password,
+            password_list,
+            attempts_remaining,
+            max_attempts,
+            failed_attempts: 0,
+            expires_at: None,
+            unlocked_at: None,
+            normalize_account: None,
+            on_transition: None,
+            sealed_accounts,
+            store_types: PhantomData,
+            state: PhantomData,
+        })
+    }
+}
+
+// Saving an unlocked manager is what marks its changes as saved, so it is only implemented on managers that have unsaved changes.
+#[cfg(feature = "std")]
+impl PasswordManager<Unlocked<Dirty>> {
+    /// Save this password manager to the file at `path`, giving back a [Saved] manager that can be locked.
+    ///
+    /// The file is written in the same format as saving a locked manager, holding the manager as it would be once locked, so it is read back with
+    /// [PasswordManager::load_from_file] and unlocked as normal.  If the file can't be written, the manager is handed back along with the error so
+    /// its changes aren't lost.
+    ///
+    /// Without the `encryption` feature the file is *NOT* encrypted.  Every account and password is written to it as plaintext, along with the master
+    /// password unless the `hashing` feature is enabled, so anyone who can read the file can read every password.  Only with `encryption` are the
+    /// accounts written encrypted with the master password.
+    pub fn save_to_file(
+        self,
+        path: impl AsRef<Path>,
+    ) -> Result<PasswordManager<Unlocked>, (Self, io::Error)> {
+        // The accounts are copied so they can be encrypted for the file the same as locking would, while this manager stays unlocked.
+        let mut snapshot = PasswordManager::<Locked> {
+            master_password: self.master_password.clone(),
+            password_list: self.password_list.clone(),
+            attempts_remaining: self.max_attempts,
+            max_attempts: self.max_attempts,
+            failed_attempts: 0,
+            expires_at: None,
+            unlocked_at: None,
+            normalize_account: None,
+            on_transition: None,
+            sealed_accounts: None,
+            store_types: PhantomData,
+            state: PhantomData,
+        };
+        snapshot.sealed_accounts = seal(&mut snapshot.master_password, &mut snapshot.password_list);
+        match snapshot.save_to_file(path) {
+            Ok(()) => Ok(self.transition()),
+            Err(error) => Err((self, error)),
+        }
+    }
+}
+
+/// Builds the contents of a file written by [PasswordManager::save_to_file].
+#[cfg(feature = "std")]
+struct FileWriter(Vec<u8>);
+
+#[cfg(feature = "std")]
+impl FileWriter {
+    fn len(&mut self, len: usize) -> io::Result<()> {
+        let len = u32::try_from(len)
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "too long to save"))?;
+        self.0.extend_from_slice(&len.to_le_bytes());
+        Ok(())
+    }
+
+    fn str(&mut self, string: &str) -> io::Result<()> {
+        self.len(string.len())?;
+        self.0.extend_from_slice(string.as_bytes());
+        Ok(())
+    }
+
+    fn optional_str(&mut self, string: Option<&str>) -> io::Result<()> {
+        match string {
+            Some(string) => {
+                self.0.push(1);
+                self.str(string)
+            }
+            None => {
+                self.0.push(0);
+                Ok(())
+            }
+        }
+    }
+
+    fn optional_u8(&mut self, value: Option<u8>) {
+        match value {
+            Some(value) => self.0.extend_from_slice(&[1, value]),
+            None => self.0.push(0),
+        }
+    }
+}
+
+/// Reads the contents of a file written by [PasswordManager::save_to_file], consuming the bytes as it goes.
+#[cfg(feature = "std")]
+struct FileReader<'a>(&'a [u8]);
+
+#[cfg(feature = "std")]
+impl<'a> FileReader<'a> {
+    fn bytes(&mut self, len: usize) -> io::Result<&'a [u8]> {
+        if self.0.len() < len {
+            return Err(invalid_data("password manager file ended unexpectedly"));
+        }
+        let (bytes, rest) = self.0.split_at(len);
+        self.0 = rest;
+        Ok(bytes)
+    }
+
+    fn u8(&mut self) -> io::Result<u8> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    fn len(&mut self) -> io::Result<usize> {
+        let bytes = self.bytes(4)?;
+        let len = u32::from_le_bytes(bytes.try_into().expect("Exactly 4 bytes were taken"));
+        Ok(len as usize)
+    }
+
+    fn string(&mut self) -> io::Result<String> {
+        let len = self.len()?;
+        let bytes = self.bytes(len)?;
+        String::from_utf8(bytes.to_vec()).map_err(|_| invalid_data("a string wasn't valid UTF-8"))
+    }
+
+    /// Read whether an optional value is present.
+    fn present(&mut self) -> io::Result<bool> {
+        match self.u8()? {
+            0 => Ok(false),
+            1 => Ok(true),
+            _ => Err(invalid_data(
+                "an optional value was neither present nor missing",
+            )),
+        }
+    }
+
+    fn optional_string(&mut self) -> io::Result<Option<String>> {
+        self.present()?.then(|| self.string()).transpose()
+    }
+
+    fn optional_u8(&mut self) -> io::Result<Option<u8>> {
+        self.present()?.then(|| self.u8()).transpose()
+    }
+}
+
+#[cfg(feature = "std")]
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+/// The master password as it is stored in a [PasswordManager].
+///
+/// Without the `hashing` feature this is just the plaintext master password.
+#[cfg(not(feature = "hashing"))]
+#[derive(Debug, Default, Clone)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(transparent)
+)]
+#[cfg_attr(feature = "zeroize", derive(zeroize::Zeroize))]
+struct MasterKey(String);
+
+#[cfg(not(feature = "hashing"))]
+impl MasterKey {
+    fn new(master_password: String) -> Self {
+        MasterKey(master_password)
+    }
+
+    /// Check whether an attempt matches the master password.
+    fn matches(&self, attempt: &str) -> bool {
+        constant_time_eq(attempt.as_bytes(), self.0.as_bytes())
+    }
+
+    /// Get the plaintext master password, so it can be checked when a builder is made from an existing manager.
+    fn plaintext(&self) -> Option<&str> {
+        Some(&self.0)
+    }
+
+    /// Write the master password for [PasswordManager::save_to_file], marked with a `0` byte as plaintext.
+    #[cfg(feature = "std")]
+    fn write_to(&self, writer: &mut FileWriter) -> io::Result<()> {
+        writer.0.push(0);
+        writer.str(&self.0)
+    }
+
+    /// Read a master password written by [MasterKey::write_to].
+    #[cfg(feature = "std")]
+    fn read_from(reader: &mut FileReader) -> io::Result<Self> {
+        match reader.u8()? {
+            0 => Ok(MasterKey(reader.string()?)),
+            _ => Err(invalid_data(
+                "the master password was saved hashed but the `hashing` feature is disabled",
+            )),
+        }
+    }
+}
+
+// Master passwords are compared in constant time, the same as when they are checked.
+#[cfg(not(feature = "hashing"))]
+impl PartialEq for MasterKey {
+    fn eq(&self, other: &Self) -> bool {
+        self.matches(&other.0)
+    }
+}
+
+/// The master password as it is stored in a [PasswordManager].
+///
+/// With the `hashing` feature only a salted SHA-256 digest of the master password is kept, so the plaintext can't be recovered from the manager.  A
+/// single round of SHA-256 is far quicker to brute force than a proper password hashing function, but it is enough to demonstrate the idea.
+///
+/// With the `encryption` feature, the key derived from the master password to encrypt the accounts is kept here too while the manager is unlocked.  It
+/// is never serialized or saved.
+#[cfg(feature = "hashing")]
+#[derive(Debug, Default, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "zeroize", derive(zeroize::Zeroize))]
+struct MasterKey {
+    salt: [u8; 16],
+    digest: [u8; 32],
+    #[cfg(feature = "encryption")]
+    #[serde(skip)]
+    key: Option<EncryptionKey>,
+}
+
+#[cfg(feature = "hashing")]
+impl MasterKey {
+    fn new(master_password: String) -> Self {
+        let salt = random_salt();
+        MasterKey {
+            digest: Self::hash(&salt, &master_password),
+            #[cfg(feature = "encryption")]
+            key: Some(EncryptionKey::derive(&master_password, &salt)),
+            salt,
+        }
+    }
+
+    /// Check whether an attempt matches the master password by hashing it with the same salt and comparing the digests.
+    fn matches(&self, attempt: &str) -> bool {
+        constant_time_eq(&Self::hash(&self.salt, attempt), &self.digest)
+    }
+
+    /// The plaintext of a hashed master password is gone, so it can't be checked.
+    fn plaintext(&self) -> Option<&str> {
+        None
+    }
+
+    /// Write the master password for [PasswordManager::save_to_file], marked with a `1` byte as hashed.
+    fn write_to(&self, writer: &mut FileWriter) -> io::Result<()> {
+        writer.0.push(1);
+        writer.0.extend_from_slice(&self.salt);
+        writer.0.extend_from_slice(&self.digest);
+        Ok(())
+    }
+
+    /// Read a master password written by [MasterKey::write_to].
+    fn read_from(reader: &mut FileReader) -> io::Result<Self> {
+        match reader.u8()? {
+            1 => Ok(MasterKey {
+                salt: reader
+                    .bytes(16)?
+                    .try_into()
+                    .expect("Exactly 16 bytes were taken"),
+                digest: reader
+                    .bytes(32)?
+                    .try_into()
+                    .expect("Exactly 32 bytes were taken"),
+                #[cfg(feature = "encryption")]
+                key: None,
+            }),
+            _ => Err(invalid_data(
+                "the master password was saved as plaintext but the `hashing` feature is enabled",
+            )),
+        }
+    }
+
+    fn hash(salt: &[u8; 16], password: &str) -> [u8; 32] {
+        use sha2::{Digest, Sha256};
+
+        let mut hasher = Sha256::new();
+        hasher.update(salt);
+        hasher.update(password.as_bytes());
+        hasher.finalize().into()
+    }
+}
+
+// Only the salt and digest are compared.  The encryption key is derived from them and the master password, so it can't differ if they match.
+#[cfg(feature = "hashing")]
+impl PartialEq for MasterKey {
+    fn eq(&self, other: &Self) -> bool {
+        self.salt == other.salt && constant_time_eq(&self.digest, &other.digest)
+    }
+}
+
+/// Generate a salt for hashing a master password.
+///
+/// This borrows the random keys the standard library generates for each [std::collections::hash_map::RandomState] to avoid pulling in a dependency
+/// just for a salt.  They are unpredictable enough for a salt but shouldn't be relied on anywhere real cryptographic randomness is needed.
+#[cfg(feature = "hashing")]
+fn random_salt() -> [u8; 16] {
+    use std::collections::hash_map::RandomState;
+    use std::hash::BuildHasher;
+
+    let mut salt = [0; 16];
+    for (i, chunk) in salt.chunks_mut(8).enumerate() {
+        let random = RandomState::new().hash_one(i);
+        chunk.copy_from_slice(&random.to_le_bytes());
+    }
+    salt
+}
+
+/// The key a [PasswordManager]'s accounts are encrypted with, derived from its master password.
+///
+/// It is derived with PBKDF2-HMAC-SHA256 using the same salt as the master password's digest.  The round count is far lower than recommended for real
+/// use to keep locking and unlocking quick, the same as the single round used for the digest.
+///
+/// Like the decrypted accounts, the key is wiped when it is dropped, as the `encryption` feature always enables `zeroize`.
+#[cfg(feature = "encryption")]
+#[derive(Clone, zeroize::Zeroize, zeroize::ZeroizeOnDrop)]
+struct EncryptionKey([u8; 32]);
+
+#[cfg(feature = "encryption")]
+impl EncryptionKey {
+    const ROUNDS: u32 = 10_000;
+
+    fn derive(master_password: &str, salt: &[u8; 16]) -> Self {
+        let mut key = [0; 32];
+        pbkdf2::pbkdf2_hmac::<sha2::Sha256>(
+            master_password.as_bytes(),
+            salt,
+            Self::ROUNDS,
+            &mut key,
+        );
+        EncryptionKey(key)
+    }
+
+    fn cipher(&self) -> chacha20poly1305::ChaCha20Poly1305 {
+        use chacha20poly1305::KeyInit;
+
+        chacha20poly1305::ChaCha20Poly1305::new(&self.0.into())
+    }
+}
+
+#[cfg(feature = "encryption")]
+impl fmt::Debug for EncryptionKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("EncryptionKey").field(&"<redacted>").finish()
+    }
+}
+
+/// A locked [PasswordManager]'s accounts, encrypted with ChaCha20-Poly1305 so they can't be read or tampered with without the master password.
+#[cfg(feature = "encryption")]
+#[derive(Clone, serde::Serialize, serde::Deserialize)]
+struct SealedAccounts {
+    nonce: [u8; 12],
+    // The number of accounts is left unencrypted so a locked manager can still say how many it holds.
+    accounts: usize,
+    // The capacity of the store the accounts came from, so it is kept when they are decrypted.  Like a plaintext map's spare capacity, it isn't saved.
+    #[serde(skip)]
+    capacity: usize,
+    ciphertext: Vec<u8>,
+}
+
+// The capacity is left out as it doesn't change which accounts are stored.
+#[cfg(feature = "encryption")]
+impl PartialEq for SealedAccounts {
+    fn eq(&self, other: &Self) -> bool {
+        self.nonce == other.nonce && self.ciphertext == other.ciphertext
+    }
+}
+
+#[cfg(feature = "encryption")]
+impl SealedAccounts {
+    /// Encrypt the accounts with `key`, wiping and removing them from the store.
+    fn seal<K: Encrypt, V: Wipe + Encrypt, B: AccountList<K, V>>(
+        key: &EncryptionKey,
+        accounts: &mut B,
+    ) -> Self {
+        use chacha20poly1305::aead::{Aead, AeadCore, OsRng};
+
+        // The accounts are written as a list of pairs rather than a map as JSON only allows strings as map keys.
+        let pairs: Vec<_> = accounts.iter().collect();
+        let mut plaintext =
+            serde_json::to_vec(&pairs).expect("Accounts and passwords should serialize");
+        let nonce = chacha20poly1305::ChaCha20Poly1305::generate_nonce(&mut OsRng);
+        let ciphertext = key
+            .cipher()
+            .encrypt(&nonce, plaintext.as_slice())
+            .expect("Encrypting in memory can't fail");
+
+        zeroize::Zeroize::zeroize(&mut plaintext);
+        accounts.iter_mut().for_each(|(_, entry)| entry.wipe());
+        let sealed_accounts = SealedAccounts {
+            nonce: nonce.into(),
+            accounts: accounts.len(),
+            capacity: accounts.capacity(),
+            ciphertext,
+        };
+        accounts.clear();
+        sealed_accounts
+    }
+
+    /// Decrypt the accounts with `key`, or return `None` if the key is wrong or the accounts have been tampered with.
+    fn open<K: Encrypt, V: Encrypt, B: SecretStore<K, V> + Default>(
+        &self,
+        key: &EncryptionKey,
+    ) -> Option<B> {
+        use chacha20poly1305::aead::Aead;
+
+        let mut plaintext = key
+            .cipher()
+            .decrypt(&self.nonce.into(), self.ciphertext.as_slice())
+            .ok()?;
+        let pairs: Result<Vec<(K, AccountEntry<V>)>, _> = serde_json::from_slice(&plaintext);
+        zeroize::Zeroize::zeroize(&mut plaintext);
+
+        let mut accounts = B::default();
+        accounts.reserve(self.capacity);
+        for (account, entry) in pairs.ok()? {
+            accounts.insert(account, entry);
+        }
+        Some(accounts)
+    }
+
+    fn len(&self) -> usize {
+        self.accounts
+    }
+
+    /// Write the encrypted accounts for [PasswordManager::save_to_file].
+    fn write_to(&self, writer: &mut FileWriter) -> io::Result<()> {
+        writer.0.extend_from_slice(&self.nonce);
+        writer.len(self.accounts)?;
+        writer.len(self.ciphertext.len())?;
+        writer.0.extend_from_slice(&self.ciphertext);
+        Ok(())
+    }
+
+    /// Read encrypted accounts written by [SealedAccounts::write_to].
+    fn read_from(reader: &mut FileReader) -> io::Result<Self> {
+        let nonce = reader
+            .bytes(12)?
+            .try_into()
+            .expect("Exactly 12 bytes were taken");
+        let accounts = reader.len()?;
+        let len = reader.len()?;
+        Ok(SealedAccounts {
+            nonce,
+            accounts,
+            capacity: 0,
+            ciphertext: reader.bytes(len)?.to_vec(),
+        })
+    }
+}
+
+/// A locked [PasswordManager]'s encrypted accounts.
+///
+/// Without the `encryption` feature accounts are never encrypted, so this can never be created.
+#[cfg(not(feature = "encryption"))]
+#[derive(Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+enum SealedAccounts {}
+
+#[cfg(not(feature = "encryption"))]
+impl SealedAccounts {
+    fn len(&self) -> usize {
+        match *self {}
+    }
+
+    #[cfg(feature = "std")]
+    fn write_to(&self, _writer: &mut FileWriter) -> io::Result<()> {
+        match *self {}
+    }
+
+    #[cfg(feature = "std")]
+    fn read_from(_reader: &mut FileReader) -> io::Result<Self> {
+        Err(invalid_data(
+            "the accounts were saved encrypted but the `encryption` feature is disabled",
+        ))
+    }
+}
+
+/// Encrypt a manager's accounts as it is locked, leaving the store empty.  Without the `encryption` feature the accounts are left as they are.
+#[cfg(not(feature = "encryption"))]
+fn seal<K, V: Wipe, B: AccountList<K, V>>(
+    _master_password: &mut MasterKey,
+    _accounts: &mut B,
+) -> Option<SealedAccounts> {
+    None
+}
+
+/// Encrypt a manager's accounts as it is locked, leaving the store empty.  The key is taken out of the master password so the locked manager can
+/// only be decrypted by unlocking it again.
+#[cfg(feature = "encryption")]
+fn seal<K: Encrypt, V: Wipe + Encrypt, B: AccountList<K, V>>(
+    master_password: &mut MasterKey,
+    accounts: &mut B,
+) -> Option<SealedAccounts> {
+    let key = master_password
+        .key
+        .take()
+        .expect("Only locked managers are missing their encryption key");
+    Some(SealedAccounts::seal(&key, accounts))
+}
+
+/// Compare two byte slices without short-circuiting on the first differing byte.
+///
+/// A plain `==` returns as soon as it finds a mismatch, so the time it takes leaks how much of a guess was correct.  This instead walks the full
+/// length of the longer slice, accumulating any differences (including a difference in length) before checking the result once at the end.
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    let mut difference = a.len() ^ b.len();
+    for i in 0..a.len().max(b.len()) {
+        let x = a.get(i).copied().unwrap_or(0);
+        let y = b.get(i).copied().unwrap_or(0);
+        difference |= usize::from(x ^ y);
+    }
+    difference == 0
+}
+
+// Locking is only implemented on unlocked password managers without unsaved changes, so the changes can't be lost by locking.
+impl<K, V: Wipe, H, B: AccountList<K, V>> PasswordManager<Unlocked, K, V, H, B> {
+    /// Lock this password manager so that the master password is required to unlock it again.
+    pub fn lock(self) -> PasswordManager<Locked, K, V, H, B>
+    where
+        K: Encrypt,
+        V: Encrypt,
+        B: Default,
+    {
+        self.into_locked()
+    }
+}
+
+// Functions only implemented on unlocked password managers, whether or not they have unsaved changes.
+impl<S: SaveState, K, V: Wipe, H, B: AccountList<K, V>> PasswordManager<Unlocked<S>, K, V, H, B> {
+    /// Check whether this manager was unlocked with a timeout that has since passed.  Managers unlocked without a timeout never expire.
+    #[cfg(feature = "std")]
+    pub fn is_expired(&self) -> bool {
+        self.expires_at
+            .is_some_and(|expires_at| Instant::now() >= expires_at)
+    }
+
+    /// Get the moment this manager was unlocked, or created if it was created already unlocked, such as to show when it was unlocked.
+    #[cfg(feature = "std")]
+    pub fn unlocked_since(&self) -> Instant {
+        self.unlocked_at
+            .expect("Unlocked managers are always given the time they were unlocked")
+    }
+
+    /// Get how long it has been since this manager was unlocked, such as to show "unlocked 5 minutes ago".
+    #[cfg(feature = "std")]
+    pub fn unlocked_for(&self) -> Duration {
+        self.unlocked_since().elapsed()
+    }
+
+    /// Compare this manager to another by how many accounts they hold, such as to sort a list of vaults by size.
+    ///
+    /// This is a named method rather than an implementation of [PartialOrd] because managers holding different accounts aren't otherwise ordered,
+    /// and `a < b` reading as "has fewer accounts" would be surprising.
+    pub fn cmp_by_size(&self, other: &Self) -> Ordering {
+        self.password_list.len().cmp(&other.password_list.len())
+    }
+}
+
+// Creating a manager from its accounts uses the default store, which the accounts can then be moved out of with [PasswordManager::with_store].
+impl<K: Eq + Hash, V: Wipe, H: BuildHasher + Default> PasswordManager<Unlocked, K, V, H> {
+    /// Create an already unlocked password manager with a master password and the accounts and passwords from an iterator.
+    ///
+    /// This skips the [PasswordManagerBuilder] for convenience, such as when setting up tests.  The master password is still kept, so it is needed to
+    /// unlock the manager again once it has been locked.  Like [PasswordManagerBuilder::with_accounts], if an account appears more than once the last
+    /// password given for it wins.
+    pub fn from_accounts<I, A, B>(master_password: impl Into<String>, accounts: I) -> Self
+    where
+        I: IntoIterator<Item = (A, B)>,
+        A: Into<K>,
+        B: Into<V>,
+    {
+        PasswordManager {
+            master_password: MasterKey::new(master_password.into()),
+            password_list: accounts
+                .into_iter()
+                .map(|(account, password)| (account.into(), AccountEntry::new(password)))
+                .collect(),
+            attempts_remaining: None,
+            max_attempts: None,
+            failed_attempts: 0,
+            expires_at: None,
+            unlocked_at: now(),
+            normalize_account: None,
+            on_transition: None,
+            sealed_accounts: None,
+            store_types: PhantomData,
+            state: PhantomData,
+        }
+    }
+}
+
+// Functions that need to look accounts up are only implemented for stores that can look them up.
+impl<K, V: Wipe, H, B: SecretStore<K, V> + Default> PasswordManager<Unlocked, K, V, H, B> {
+    /// Insert a new account and password into the password manager, giving back a [Dirty] manager as it now has unsaved changes.
+    ///
+    /// If the account already exists only its password is replaced, so its URL, notes and tags are kept.  The old password is added to the end of the
+    /// account's password history.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the account is a [String] that is empty or only whitespace.  Use [PasswordManager::try_insert] to get an error instead.
+    pub fn insert(
+        self,
+        account: impl Into<K>,
+        password: impl Into<V>,
+    ) -> PasswordManager<Unlocked<Dirty>, K, V, H, B>
+    where
+        K: 'static,
+    {
+        let mut manager = self.edit();
+        manager.insert(account, password);
+        manager
+    }
+
+    /// Remove an account from the password manager, giving back a [Dirty] manager as it now has unsaved changes along with the account's password
+    /// if it existed.
+    #[allow(clippy::type_complexity)]
+    pub fn remove_account<Q>(
+        self,
+        account: &Q,
+    ) -> (PasswordManager<Unlocked<Dirty>, K, V, H, B>, Option<V>)
+    where
+        K: Borrow<Q>,
+        Q: Hash + Ord + ToOwned<Owned = K> + ?Sized,
+    {
+        let mut manager = self.edit();
+        let password = manager.remove_account(account);
+        (manager, password)
+    }
+
+    /// Give back a [Dirty] manager so accounts can be changed in place, such as with [PasswordManager::set_url] or [PasswordManager::clear].
+    ///
+    /// Nothing is changed yet, but the manager is treated as having unsaved changes from here on.  It has to be saved with
+    /// [PasswordManager::save_to_file] or marked as saved with [PasswordManager::mark_saved] before it can be locked.
+    pub fn edit(self) -> PasswordManager<Unlocked<Dirty>, K, V, H, B> {
+        self.transition()
+    }
+}
+
+// Functions that only read from or manage the memory of an unlocked password manager, whether or not it has unsaved changes.
+impl<S: SaveState, K, V: Wipe, H, B: SecretStore<K, V>> PasswordManager<Unlocked<S>, K, V, H, B> {
+    /// Get a single password given the account, or an [Expired] error if the manager was unlocked with a timeout that has since passed.
+    #[cfg(feature = "std")]
+    pub fn get_password_checked<Q>(&self, account: &Q) -> Result<Option<V>, Expired>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Ord + ToOwned<Owned = K> + ?Sized,
+        V: Clone,
+    {
+        if self.is_expired() {
+            return Err(Expired);
+        }
+        Ok(self.get_password(account))
+    }
+
+    /// Make room for at least `additional` more accounts, so inserting many accounts at once doesn't grow the account list over and over.
+    pub fn reserve(&mut self, additional: usize) {
+        self.password_list.reserve(additional);
+    }
+
+    /// Get the number of accounts the password manager can hold without having to grow.
+    pub fn capacity(&self) -> usize {
+        self.password_list.capacity()
+    }
+
+    /// Shrink the account list to fit the accounts left in it, such as after removing many accounts, so the memory they used can be freed.
+    pub fn shrink_to_fit(&mut self) {
+        self.password_list.shrink_to_fit();
+    }
+}
+
+// A manager that already has unsaved changes stays dirty as more are made, so it is changed in place rather than moved into another state.  Functions
+// that change accounts in place are only implemented here, so a changed manager can't be locked without saving it first.  Use
+// [PasswordManager::edit] to get one from a [Saved] manager.
+impl<K, V: Wipe, H, B: SecretStore<K, V>> PasswordManager<Unlocked<Dirty>, K, V, H, B> {
+    /// Insert a new account and password into the password manager, the same as inserting into a [Saved] manager.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the account is a [String] that is empty or only whitespace.  Use [PasswordManager::try_insert] to get an error instead.
+    pub fn insert(&mut self, account: impl Into<K>, password: impl Into<V>)
+    where
+        K: 'static,
+    {
+        let account = account.into();
+        // Only `String` accounts can be blank, and checking through `Any` keeps managers with other account types, such as numbers, working.
+        if let Some(account) = (&account as &dyn Any).downcast_ref::<String>() {
+            assert!(
+                !account.trim().is_empty(),
+                "Account names can't be empty or only whitespace"
+            );
+        }
+        let (account, name) = self.normalize(account);
+        // The password is only taken by the closure if the account is new, otherwise it replaces the existing one below.
+        let mut password = Some(password.into());
+        let entry = self.password_list.get_or_insert_with(account, || {
+            AccountEntry::new(password.take().expect("The password is only taken once")).named(name)
+        });
+        if let Some(password) = password {
+            let old = mem::replace(&mut entry.password, password);
+            entry.history.push(old);
+        }
+    }
+
+    /// Remove an account from the password manager, returning its password if the account existed.
+    pub fn remove_account<Q>(&mut self, account: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Ord + ToOwned<Owned = K> + ?Sized,
+    {
+        let key = self.account_key(account);
+        self.password_list.remove(&*key).map(|entry| entry.password)
+    }
+
+    /// Mark this password manager's changes as saved without saving them, such as when they were saved some other way, so it can be locked.
+    ///
+    /// The changes are *NOT* thrown away.  They stay in the manager, so the locked manager still holds them and encrypts them along with every
+    /// other account if the `encryption` feature is enabled.
+    pub fn mark_saved(self) -> PasswordManager<Unlocked, K, V, H, B>
+    where
+        B: Default,
+    {
+        self.transition()
+    }
+
+    /// Replace the master password used to unlock this password manager.
+    ///
+    /// This is only callable on an unlocked manager, so the caller must have already proven they know the old master password by unlocking it.
+    pub fn change_master_password(&mut self, new_password: impl Into<String>) {
+        #[cfg(feature = "zeroize")]
+        zeroize::Zeroize::zeroize(&mut self.master_password);
+        self.master_password = MasterKey::new(new_password.into());
+    }
+
+    /// Get a mutable reference to a single password given the account, so it can be edited in place.
+    ///
+    /// The manager stays mutably borrowed for as long as the reference is held, so it can't be locked or read from while a password is being edited.
+    pub fn get_password_mut<Q>(&mut self, account: &Q) -> Option<&mut V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Ord + ToOwned<Owned = K> + ?Sized,
+    {
+        self.get_entry_mut(account).map(|entry| &mut entry.password)
+    }
+
+    /// Get a mutable reference to everything stored about an account, so any of its fields can be edited in place.
+    pub fn get_entry_mut<Q>(&mut self, account: &Q) -> Option<&mut AccountEntry<V>>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Ord + ToOwned<Owned = K> + ?Sized,
+    {
+        let key = self.account_key(account);
+        self.password_list.get_mut(&*key)
+    }
+
+    /// Remove every account from the password manager, keeping its master password.
+    ///
+    /// With the `zeroize` feature enabled, the removed passwords are overwritten with zeroes rather than just dropped.
+    pub fn clear(&mut self) {
+        #[cfg(feature = "zeroize")]
+        self.password_list
+            .iter_mut()
+            .for_each(|(_, entry)| entry.wipe());
+        self.password_list.clear();
+    }
+
+    /// Insert a new account and password into the password manager only if the account doesn't exist yet, returning whether it was inserted.
+    ///
+    /// An existing account is left exactly as it was, so this can't accidentally overwrite a password.
+    pub fn insert_if_absent(&mut self, account: impl Into<K>, password: impl Into<V>) -> bool {
+        let (account, name) = self.normalize(account.into());
+        let mut inserted = false;
+        self.password_list.get_or_insert_with(account, || {
+            inserted = true;
+            AccountEntry::new(password).named(name)
+        });
+        inserted
+    }
+
+    /// Insert a new account and password into the password manager, replacing the password of an account that already exists and adding the old
+    /// one to its history, or return an error without changing anything if the account name is empty or only whitespace.
+    pub fn try_insert(
+        &mut self,
+        account: impl Into<K>,
+        password: impl Into<V>,
+    ) -> Result<(), InsertError>
+    where
+        K: AsRef<str> + 'static,
+    {
+        let account = account.into();
+        if account.as_ref().trim().is_empty() {
+            return Err(InsertError::EmptyAccount);
+        }
+        self.insert(account, password);
+        Ok(())
+    }
+
+    /// Insert a new account into the password manager along with its URL, notes and tags, replacing everything stored about it if it already exists.
+    pub fn insert_entry(&mut self, account: impl Into<K>, entry: AccountEntry<V>) {
+        let (account, name) = self.normalize(account.into());
+        self.password_list.insert(account, entry.named(name));
+    }
+
+    /// Insert every account from a builder that has no master password, so a builder can be used purely to collect accounts.
+    ///
+    /// Like [PasswordManager::insert_entry], accounts that already exist have everything stored about them replaced.  The builder's other settings,
+    /// such as its maximum attempts, are ignored and the master password is left as it is.
+    pub fn apply_accounts_from(
+        &mut self,
+        builder: PasswordManagerBuilder<MissingPassword, K, V, H>,
+    ) {
+        for (account, entry) in builder.password_list {
+            self.insert_entry(account, entry);
+        }
+    }
+
+    /// Set the URL of an account, returning whether the account existed.
+    pub fn set_url<Q>(&mut self, account: &Q, url: Option<String>) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Hash + Ord + ToOwned<Owned = K> + ?Sized,
+    {
+        self.get_entry_mut(account)
+            .map(|entry| entry.url = url)
+            .is_some()
+    }
+
+    /// Set the notes of an account, returning whether the account existed.
+    pub fn set_notes<Q>(&mut self, account: &Q, notes: Option<String>) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Hash + Ord + ToOwned<Owned = K> + ?Sized,
+    {
+        self.get_entry_mut(account)
+            .map(|entry| entry.notes = notes)
+            .is_some()
+    }
+
+    /// Add a tag to an account if it doesn't already have it, returning whether the account existed.
+    pub fn add_tag<Q>(&mut self, account: &Q, tag: impl Into<String>) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Hash + Ord + ToOwned<Owned = K> + ?Sized,
+    {
+        let tag = tag.into();
+        self.get_entry_mut(account)
+            .map(|entry| {
+                if !entry.tags.contains(&tag) {
+                    entry.tags.push(tag);
+                }
+            })
+            .is_some()
+    }
+
+    /// Remove a tag from an account, returning whether the account had it.
+    pub fn remove_tag<Q>(&mut self, account: &Q, tag: &str) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Hash + Ord + ToOwned<Owned = K> + ?Sized,
+    {
+        self.get_entry_mut(account).is_some_and(|entry| {
+            let len = entry.tags.len();
+            entry.tags.retain(|t| t != tag);
+            entry.tags.len() != len
+        })
+    }
+
+    /// Remove several accounts from the password manager, returning the passwords of the ones that existed.
+    ///
+    /// Accounts that don't exist are skipped.
+    pub fn remove_accounts<'a, Q, I>(&mut self, accounts: I) -> HashMap<K, V, H>
+    where
+        I: IntoIterator<Item = &'a Q>,
+        K: Borrow<Q> + Eq + Hash,
+        Q: Hash + Ord + ToOwned<Owned = K> + ?Sized + 'a,
+        H: BuildHasher + Default,
+    {
+        accounts
+            .into_iter()
+            .filter_map(|account| {
+                let key = self.account_key(account);
+                self.password_list.remove_entry(&*key)
+            })
+            .map(|(account, entry)| (account, entry.password))
+            .collect()
+    }
+
+    /// Move the password stored under one account name to another, returning whether the old account existed.
+    ///
+    /// The account's URL, notes and tags move along with its password.  If an account already exists under the new name, it is overwritten.
+    pub fn rename_account<Q>(&mut self, old: &Q, new: impl Into<K>) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Hash + Ord + ToOwned<Owned = K> + ?Sized,
+    {
+        let key = self.account_key(old);
+        match self.password_list.remove(&*key) {
+            Some(entry) => {
+                self.insert_entry(new, entry);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Move every account from another unlocked password manager into this one, using `strategy` to decide which password to keep when both have
+    /// the same account.
+    ///
+    /// The other manager is consumed and its master password is discarded, so this manager's master password is still the only one that unlocks it.
+    pub fn merge<T>(
+        &mut self,
+        mut other: PasswordManager<Unlocked<T>, K, V, H, B>,
+        strategy: MergeStrategy,
+    ) {
+        // The accounts are drained rather than moved out as `other` may implement `Drop`.  Its master password is wiped when it is dropped at the
+        // end.
+        let accounts = other.password_list.drain();
+        match strategy {
+            MergeStrategy::KeepExisting => {
+                for (account, entry) in accounts {
+                    let (account, name) = self.normalize(account);
+                    self.password_list
+                        .get_or_insert_with(account, || entry.named(name));
+                }
+            }
+            MergeStrategy::Overwrite => {
+                for (account, entry) in accounts {
+                    self.insert_entry(account, entry);
+                }
+            }
+        }
+    }
+
+    /// Normalize an account name into the key it is stored under, along with the name to keep in its entry if that isn't the key.
+    fn normalize(&self, account: K) -> (K, Option<String>) {
+        normalize(self.normalize_account, account)
+    }
+}
+
+// Entries borrow from the map itself, so they are only implemented for managers using the default store.  Like the other functions that change
+// accounts in place, they are only implemented on managers that already have unsaved changes.
+impl<K: Eq + Hash, V: Wipe, H: BuildHasher> PasswordManager<Unlocked<Dirty>, K, V, H> {
+    /// Get the given account's [Entry] for inserting or changing its password in place, without looking it up more than once.
+    pub fn entry(&mut self, account: impl Into<K>) -> Entry<'_, K, V, H> {
+        let (account, name) = self.normalize(account.into());
+        Entry {
+            entry: self.password_list.entry(account),
+            name,
+            hasher: PhantomData,
+        }
+    }
+}
+
+impl<K, V: Wipe, H, B: AccountList<K, V> + Default> Default
+    for PasswordManager<NeedsSetup, K, V, H, B>
+{
+    fn default() -> Self {
+        PasswordManager {
+            master_password: MasterKey::default(),
+            password_list: B::default(),
+            attempts_remaining: None,
+            max_attempts: None,
+            failed_attempts: 0,
+            expires_at: None,
+            unlocked_at: None,
+            normalize_account: None,
+            on_transition: None,
+            sealed_accounts: None,
+            store_types: PhantomData,
+            state: PhantomData,
+        }
+    }
+}
+
+// Functions only implemented on password managers that haven't been set up yet.
+impl<K, V: Wipe, H, B: AccountList<K, V> + Default> PasswordManager<NeedsSetup, K, V, H, B> {
+    /// Create an empty password manager with no master password, which has to be set before it can be used.
+    ///
+    /// Unlike [PasswordManagerBuilder], the master password doesn't have to be known when the manager is created, so it can be chosen later such as
+    /// when a user runs an app for the first time.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the master password of this password manager, giving a locked manager that unlocks with it.
+    pub fn set_master_password(
+        mut self,
+        master_password: impl Into<String>,
+    ) -> PasswordManager<Locked, K, V, H, B>
+    where
+        K: Encrypt,
+        V: Encrypt,
+    {
+        self.master_password = MasterKey::new(master_password.into());
+        self.sealed_accounts = seal(&mut self.master_password, &mut self.password_list);
+        self.transition()
+    }
+}
+
+// Freezing is only implemented on unlocked password managers, as the accounts have to be readable to be kept in the snapshot.
+impl<K, V: Wipe, H, B: AccountList<K, V> + Default> PasswordManager<Unlocked, K, V, H, B> {
+    /// Freeze this password manager into a snapshot whose passwords can still be read but can never be changed.
+    ///
+    /// This is one way.  A [Frozen] manager has no methods to lock, unlock or change it, so it stays exactly as it was when it was frozen.
+    pub fn freeze(mut self) -> PasswordManager<Frozen, K, V, H, B> {
+        // A snapshot is kept for as long as it's needed, so it shouldn't stop giving out passwords when the unlock timeout passes.
+        self.expires_at = None;
+        self.transition()
+    }
+}
+
+// Functions only implemented on read-only password managers.
+impl<K, V: Wipe, H, B: AccountList<K, V>> PasswordManager<ReadOnly, K, V, H, B> {
+    /// Lock this password manager so that the master password is required to unlock it again.
+    pub fn lock(self) -> PasswordManager<Locked, K, V, H, B>
+    where
+        K: Encrypt,
+        V: Encrypt,
+        B: Default,
+    {
+        self.into_locked()
+    }
+}
+
+// Functions that read accounts are implemented for every state that allows reading, which is unlocked, read-only and frozen managers.
+impl<State: Readable, K, V: Wipe, H, B: SecretStore<K, V>> PasswordManager<State, K, V, H, B> {
+    /// Get a list of the stored accounts and their passwords.
+    pub fn get_passwords(&self) -> HashMap<K, V, H>
+    where
+        K: Clone + Eq + Hash,
+        V: Clone,
+        H: BuildHasher + Default,
+    {
+        self.password_list
+            .iter()
+            .map(|(account, entry)| (account.clone(), entry.password.clone()))
+            .collect()
+    }
+
+    /// Get a single password given the account.
+    ///
+    /// Like [HashMap::get], the account can be given as any borrowed form of the account type, so a `&str` can be used to look up a `String` account.
+    pub fn get_password<Q>(&self, account: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Ord + ToOwned<Owned = K> + ?Sized,
+        V: Clone,
+    {
+        self.get_entry(account).map(|entry| entry.password.clone())
+    }
+
+    /// Get the passwords of several accounts at once, mapping every account asked for to its password or [None] if it doesn't exist.
+    ///
+    /// The accounts are returned as they were given rather than as they are stored, so each can be looked up in the result the same way it was
+    /// asked for.
+    pub fn get_many<'a, Q, I>(&self, accounts: I) -> HashMap<K, Option<V>, H>
+    where
+        I: IntoIterator<Item = &'a Q>,
+        K: Borrow<Q>,
+        Q: Hash + Ord + ToOwned<Owned = K> + ?Sized + 'a,
+        K: Eq + Hash,
+        V: Clone,
+        H: BuildHasher + Default,
+    {
+        accounts
+            .into_iter()
+            .map(|account| (account.to_owned(), self.get_password(account)))
+            .collect()
+    }
+
+    /// Get everything stored about an account, including its password, URL, notes and tags.
+    pub fn get_entry<Q>(&self, account: &Q) -> Option<&AccountEntry<V>>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Ord + ToOwned<Owned = K> + ?Sized,
+    {
+        self.password_list.get(&*self.account_key(account))
+    }
+
+    /// Iterate over the accounts that have been given a tag.
+    ///
+    /// The accounts borrow from the manager rather than `tag`, so they can outlive a temporary tag.
+    pub fn accounts_with_tag<'a, 't>(
+        &'a self,
+        tag: &'t str,
+    ) -> impl Iterator<Item = &'a K> + use<'a, 't, State, K, V, H, B> {
+        self.password_list
+            .iter()
+            .filter(move |(_, entry)| entry.tags.iter().any(|t| t == tag))
+            .map(|(account, _)| account)
+    }
+
+    /// Get the passwords an account used to have, from oldest to most recent, or [None] if the account doesn't exist.
+    pub fn password_history<Q>(&self, account: &Q) -> Option<&[V]>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Ord + ToOwned<Owned = K> + ?Sized,
+    {
+        self.get_entry(account)
+            .map(|entry| entry.history.as_slice())
+    }
+
+    /// Check whether an account has used a password before, so callers can warn about reusing it.  The current password doesn't count.
+    pub fn was_previously_used<Q, C>(&self, account: &Q, candidate: &C) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Hash + Ord + ToOwned<Owned = K> + ?Sized,
+        V: PartialEq<C>,
+        C: ?Sized,
+    {
+        self.get_entry(account)
+            .is_some_and(|entry| entry.history.iter().any(|old| old == candidate))
+    }
+
+    /// Check whether an account is stored in the password manager.
+    pub fn contains_account<Q>(&self, account: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Hash + Ord + ToOwned<Owned = K> + ?Sized,
+    {
+        self.password_list.contains_key(&*self.account_key(account))
+    }
+
+    /// Compare the accounts in this manager with those in `other`, listing which are only in one of them and which are in both with different
+    /// passwords.
+    ///
+    /// Only the passwords are compared, so accounts whose URL, notes or tags differ aren't listed.
+    pub fn difference(&self, other: &PasswordManager<Unlocked, K, V, H, B>) -> VaultDiff<K>
+    where
+        K: Clone + Hash + Ord,
+        V: PartialEq,
+    {
+        let mut diff = VaultDiff {
+            only_in_self: Vec::new(),
+            only_in_other: Vec::new(),
+            changed: Vec::new(),
+        };
+        for (account, entry) in self.password_list.iter() {
+            match other.password_list.get(account) {
+                None => diff.only_in_self.push(account.clone()),
+                Some(other_entry) if other_entry.password != entry.password => {
+                    diff.changed.push(account.clone())
+                }
+                Some(_) => {}
+            }
+        }
+        diff.only_in_other = other
+            .password_list
+            .iter()
+            .map(|(account, _)| account)
+            .filter(|account| !self.password_list.contains_key(*account))
+            .cloned()
+            .collect();
+        diff
+    }
+
+    /// Get the key an account is looked up by, only allocating if account names are normalized.
+    fn account_key<'a, Q>(&self, account: &'a Q) -> Cow<'a, Q>
+    where
+        Q: ToOwned<Owned = K> + ?Sized,
+    {
+        match self.normalize_account {
+            Some(normalize) => Cow::Owned(normalize(account.to_owned()).0),
+            None => Cow::Borrowed(account),
+        }
+    }
+}
+
+// Functions that read accounts from any store, implemented for every state that allows reading.
+impl<State: Readable, K, V: Wipe, H, B: AccountList<K, V>> PasswordManager<State, K, V, H, B> {
+    /// Get the number of accounts stored in the password manager.
+    pub fn len(&self) -> usize {
+        self.password_list.len()
+    }
+
+    /// Check whether the password manager has no accounts stored in it.
+    pub fn is_empty(&self) -> bool {
+        self.password_list.is_empty()
+    }
+}
+
+// Functions that read accounts using the default `String` account and `SecretString` password types from any store, implemented for every state
+// that allows reading.
+impl<State: Readable, B: SecretStore<String, SecretString>>
+    PasswordManager<State, String, SecretString, DefaultHashBuilder, B>
+{
+    /// Iterate over the stored accounts and their passwords without cloning them.
+    ///
+    /// Accounts are listed in the order the store keeps them, so a manager using a [BTreeMap](alloc::collections::BTreeMap) lists them sorted.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.password_list
+            .iter()
+            .map(|(account, entry)| (entry.name_or(account), entry.password.expose()))
+    }
+
+    /// Iterate over the names of the stored accounts.
+    pub fn accounts(&self) -> impl Iterator<Item = &str> {
+        self.password_list
+            .iter()
+            .map(|(account, entry)| entry.name_or(account))
+    }
+
+    /// Get a single password given the account, borrowed rather than cloned.
+    ///
+    /// This is cheaper than [PasswordManager::get_password] when the password only needs to be read, such as to display it.
+    pub fn get_password_ref(&self, account: &str) -> Option<&str> {
+        self.get_entry(account).map(|entry| entry.password.expose())
+    }
+
+    /// Get the names of the stored accounts sorted lexicographically, so they can be displayed in the same order every time.
+    pub fn account_names_sorted(&self) -> Vec<&str> {
+        let mut names: Vec<&str> = self.accounts().collect();
+        names.sort_unstable();
+        names
+    }
+
+    /// Get the names of the accounts that have been given a tag, sorted lexicographically.
+    pub fn by_tag(&self, tag: &str) -> Vec<&str> {
+        let mut names: Vec<&str> = self
+            .password_list
+            .iter()
+            .filter(|(_, entry)| entry.tags.iter().any(|t| t == tag))
+            .map(|(account, entry)| entry.name_or(account))
+            .collect();
+        names.sort_unstable();
+        names
+    }
+
+    /// Get the password of the first of `accounts` that exists, such as for a service stored under one of several aliases.
+    pub fn get_password_any<'a>(
+        &self,
+        accounts: impl IntoIterator<Item = &'a str>,
+    ) -> Option<&str> {
+        accounts
+            .into_iter()
+            .find_map(|account| self.get_password_ref(account))
+    }
+
+    /// Find an account whose name and password match `pred`, returning both.  If several match, which one is returned is unspecified.
+    pub fn find_account<F: Fn(&str, &str) -> bool>(&self, pred: F) -> Option<(&str, &str)> {
+        self.iter()
+            .find(|&(account, password)| pred(account, password))
+    }
+
+    /// Get the names of the accounts whose password is exactly `password`, sorted lexicographically, such as to warn that a password is reused.
+    pub fn accounts_with_password(&self, password: &str) -> Vec<&str> {
+        // Passwords are compared as secrets so the time taken doesn't leak how close `password` is to any of them.
+        let mut names: Vec<&str> = self
+            .password_list
+            .iter()
+            .filter(|(_, entry)| entry.password == *password)
+            .map(|(account, entry)| entry.name_or(account))
+            .collect();
+        names.sort_unstable();
+        names
+    }
+
+    /// Map every password used by more than one account to the names of those accounts, sorted lexicographically.  Passwords that are only
+    /// used once are left out, so an empty map means no password is reused.
+    pub fn reused_passwords(&self) -> HashMap<String, Vec<String>> {
+        let mut by_password: HashMap<&str, Vec<&str>> = HashMap::default();
+        for (account, password) in self.iter() {
+            by_password.entry(password).or_default().push(account);
+        }
+        by_password
+            .into_iter()
+            .filter(|(_, accounts)| accounts.len() > 1)
+            .map(|(password, mut accounts)| {
+                accounts.sort_unstable();
+                (
+                    password.to_string(),
+                    accounts.into_iter().map(String::from).collect(),
+                )
+            })
+            .collect()
+    }
+
+    /// Count the accounts whose passwords [evaluate_strength] rates as [PasswordStrength::Weak].
+    pub fn weak_password_count(&self) -> usize {
+        self.weak_accounts_unsorted().count()
+    }
+
+    /// Get the names of the accounts whose passwords [evaluate_strength] rates as [PasswordStrength::Weak], sorted lexicographically.
+    pub fn weak_accounts(&self) -> Vec<&str> {
+        let mut names: Vec<&str> = self.weak_accounts_unsorted().collect();
+        names.sort_unstable();
+        names
+    }
+
+    /// Estimate how many bits of entropy an account's password has, or [None] if the account doesn't exist.
+    ///
+    /// The estimate is the password's length multiplied by `log2` of the number of characters it could have been drawn from, counting 26 for
+    /// lowercase letters, 26 for uppercase letters, 10 for digits and 33 for symbols if the password uses any of them.  Like [evaluate_strength] it
+    /// is only a rough guide, as it assumes every character was picked at random.  It needs the `std` feature for floating point logarithms.
+    #[cfg(feature = "std")]
+    pub fn password_entropy(&self, account: &str) -> Option<f64> {
+        self.get_password_ref(account).map(estimate_entropy)
+    }
+
+    /// Get the mean of [PasswordManager::password_entropy] across every account, or `0.0` if there are no accounts.
+    #[cfg(feature = "std")]
+    pub fn average_entropy(&self) -> f64 {
+        if self.password_list.is_empty() {
+            return 0.0;
+        }
+        let total: f64 = self
+            .iter()
+            .map(|(_, password)| estimate_entropy(password))
+            .sum();
+        total / self.password_list.len() as f64
+    }
+
+    /// Iterate over the names of the accounts with weak passwords in no particular order.
+    fn weak_accounts_unsorted(&self) -> impl Iterator<Item = &str> {
+        self.iter()
+            .filter(|(_, password)| evaluate_strength(password) == PasswordStrength::Weak)
+            .map(|(account, _)| account)
+    }
+
+    /// Get the names of the accounts containing `query`, ignoring case, sorted alphabetically.
+    ///
+    /// Only account names are searched and returned, so a search can't reveal anything about the passwords.
+    pub fn search(&self, query: &str) -> Vec<&str> {
+        let query = query.to_lowercase();
+        let mut matches: Vec<&str> = self
+            .accounts()
+            .filter(|account| account.to_lowercase().contains(&query))
+            .collect();
+        // Names are compared ignoring case first so differently cased accounts sort together, then exactly so the order never depends on the
+        // order of the underlying map.
+        matches.sort_by(|a, b| {
+            a.to_lowercase()
+                .cmp(&b.to_lowercase())
+                .then_with(|| a.cmp(b))
+        });
+        matches
+    }
+
+    /// Export the stored accounts and passwords as CSV, with one `account,password` line per account and no header.
+    ///
+    /// Fields containing commas, quotes or line breaks are wrapped in quotes, with any quotes inside them doubled.  The output contains every password
+    /// in plaintext, so it should be handled as carefully as the unlocked manager itself.
+    pub fn export_csv(&self) -> String {
+        let mut csv = String::new();
+        for (account, password) in self.iter() {
+            write_csv_field(&mut csv, account);
+            csv.push(',');
+            write_csv_field(&mut csv, password);
+            csv.push('\n');
+        }
+        csv
+    }
+}
+
+// Functions only implemented on unlocked password managers using the default `String` account and `SecretString` password types.
+impl PasswordManager<Unlocked> {
+    /// Create an already unlocked password manager with a master password and the accounts and passwords from CSV in the format written by
+    /// [PasswordManager::export_csv].
+    ///
+    /// Blank lines are skipped.  If an account appears more than once the last password given for it wins.
+    pub fn from_csv(master_password: impl Into<String>, csv: &str) -> Result<Self, CsvError> {
+        let records = parse_csv(csv)?;
+        let mut accounts = Vec::with_capacity(records.len());
+        for (line, record) in records {
+            let [account, password] =
+                <[String; 2]>::try_from(record).map_err(|record| CsvError::WrongFieldCount {
+                    line,
+                    fields: record.len(),
+                })?;
+            accounts.push((account, password));
+        }
+        Ok(Self::from_accounts(master_password, accounts))
+    }
+}
+
+// Functions only implemented on unlocked password managers using the default types, whether or not they have unsaved changes.
+impl<S: SaveState, B: SecretStore<String, SecretString>>
+    PasswordManager<Unlocked<S>, String, SecretString, DefaultHashBuilder, B>
+{
+    /// Get a single password given the account, borrowed rather than cloned, or an [AccountNotFound] error naming the account if it doesn't exist.
+    ///
+    /// This is the same as [PasswordManager::get_password_ref] for callers that want an error they can log or pass on with `?`.
+    pub fn try_get_password(&self, account: &str) -> Result<&str, AccountNotFound> {
+        self.get_password_ref(account)
+            .ok_or_else(|| AccountNotFound::new(account))
+    }
+
+    /// Consume the password manager and return its master password and accounts with their passwords, such as to hand them to other code.
+    ///
+    /// This is an escape hatch that gives up every guarantee the manager makes, and [PasswordManager::from_accounts] turns the parts back into a
+    /// manager.  Like [PasswordManager::drain], only the current passwords are returned.  It isn't available with the `hashing` feature as the
+    /// master password isn't kept in plaintext.
+    #[cfg(not(feature = "hashing"))]
+    pub fn into_inner(mut self) -> (String, HashMap<String, String>) {
+        let accounts = self.drain_passwords();
+        (mem::take(&mut self.master_password.0), accounts)
+    }
+
+    /// Remove every account and return them with their current passwords, wiping their password histories with the `zeroize` feature.
+    fn drain_passwords(&mut self) -> HashMap<String, String> {
+        self.password_list
+            .drain()
+            .map(|(account, mut entry)| {
+                let password = mem::take(entry.password.expose_mut());
+                #[cfg(feature = "zeroize")]
+                entry.wipe();
+                (entry.name.unwrap_or(account), password)
+            })
+            .collect()
+    }
+}
+
+// Functions that change accounts using the default types in place, only implemented on managers that already have unsaved changes.
+impl<B: SecretStore<String, SecretString>>
+    PasswordManager<Unlocked<Dirty>, String, SecretString, DefaultHashBuilder, B>
+{
+    /// Keep only the accounts for which `f` returns `true`, given each account's name and password.
+    pub fn retain<F: FnMut(&str, &str) -> bool>(&mut self, mut f: F) {
+        self.password_list
+            .retain(|account, entry| f(entry.name_or(account), entry.password.expose()));
+    }
+
+    /// Get an account's password, first inserting the password returned by `default` if the account doesn't exist.
+    ///
+    /// `default` is only called when the account is missing, so an expensive password (such as one fetched from elsewhere) is only computed once.
+    pub fn get_password_or_insert_with<F: FnOnce() -> String>(
+        &mut self,
+        account: impl Into<String>,
+        default: F,
+    ) -> &str {
+        let (account, name) = self.normalize(account.into());
+        self.password_list
+            .get_or_insert_with(account, || AccountEntry::new(default()).named(name))
+            .password
+            .expose()
+    }
+
+    /// Call `f` with every account's name and a mutable reference to its password, so every password can be changed at once such as during a
+    /// migration.
+    ///
+    /// Like [PasswordManager::get_password_mut], the passwords are edited in place so the old ones aren't added to the accounts' histories.
+    pub fn bulk_update<F: FnMut(&str, &mut String)>(&mut self, mut f: F) {
+        for (account, entry) in self.password_list.iter_mut() {
+            let AccountEntry { password, name, .. } = entry;
+            f(name.as_deref().unwrap_or(account), password.expose_mut());
+        }
+    }
+
+    /// Swap the passwords of two accounts that already exist, or return an [AccountNotFound] error naming the first one that doesn't.
+    ///
+    /// Only the current passwords are swapped.  The accounts' URLs, notes, tags and password histories stay where they are.
+    pub fn swap_passwords(&mut self, a: &str, b: &str) -> Result<(), AccountNotFound> {
+        for account in [a, b] {
+            if !self.contains_account(account) {
+                return Err(AccountNotFound::new(account));
+            }
+        }
+        if self.account_key(a) == self.account_key(b) {
+            return Ok(());
+        }
+        // The map can't lend out two entries mutably at once, so one password is taken out while the other is swapped in.
+        fn password<'a, B: SecretStore<String, SecretString>>(
+            manager: &'a mut PasswordManager<
+                Unlocked<Dirty>,
+                String,
+                SecretString,
+                DefaultHashBuilder,
+                B,
+            >,
+            account: &str,
+        ) -> &'a mut String {
+            manager
+                .get_entry_mut(account)
+                .expect("Both accounts were checked to exist")
+                .password
+                .expose_mut()
+        }
+        let first = mem::take(password(self, a));
+        let second = mem::replace(password(self, b), first);
+        *password(self, a) = second;
+        Ok(())
+    }
+
+    /// Transform every password with `f`, such as to re-encode them during a migration, keeping the account names and master password.
+    ///
+    /// Unlike [PasswordManager::bulk_update], `f` only sees each password, and the accounts' previous passwords are transformed too so the whole
+    /// history stays in the same form.
+    pub fn map_passwords<F: Fn(&str) -> String>(mut self, f: F) -> Self {
+        for (_, entry) in self.password_list.iter_mut() {
+            for password in iter::once(&mut entry.password).chain(&mut entry.history) {
+                let mapped = f(password.expose());
+                #[cfg(feature = "zeroize")]
+                zeroize::Zeroize::zeroize(password.expose_mut());
+                *password.expose_mut() = mapped;
+            }
+        }
+        self
+    }
+
+    /// Replace the password of an account that already exists, returning its old password, or return an [AccountNotFound] error without adding
+    /// the account if it doesn't.
+    ///
+    /// Unlike [PasswordManager::insert], a mistyped account name can't silently create a new account.  The old password is still added to the end
+    /// of the account's password history.
+    pub fn replace_password(
+        &mut self,
+        account: &str,
+        new: impl Into<String>,
+    ) -> Result<String, AccountNotFound> {
+        let entry = self
+            .get_entry_mut(account)
+            .ok_or_else(|| AccountNotFound::new(account))?;
+        let old = mem::replace(&mut entry.password, new.into().into());
+        let password = old.expose().to_string();
+        entry.history.push(old);
+        Ok(password)
+    }
+
+    /// Remove every account from the password manager and return them with their passwords, keeping its master password.
+    ///
+    /// Only the current passwords are returned.  With the `zeroize` feature enabled, the accounts' password histories are overwritten with zeroes
+    /// like [PasswordManager::clear] does.
+    pub fn drain(&mut self) -> HashMap<String, String> {
+        self.drain_passwords()
+    }
+
+    /// Replace every account's password with a freshly generated one `len` characters long, such as for periodic rotation.  The old passwords are
+    /// added to the end of the accounts' password histories.
+    ///
+    /// See [generate_password] for how the passwords are generated.  If `opts` has a seed, the accounts are rotated in order of their names with
+    /// the seed counting up from one account to the next, so they all get different passwords but the same ones every time.
+    #[cfg(feature = "std")]
+    pub fn rotate_all_passwords(&mut self, len: usize, opts: CharsetOptions) {
+        let mut entries: Vec<_> = self.password_list.iter_mut().collect();
+        entries.sort_unstable_by_key(|(account, _)| *account);
+        for (i, (_, entry)) in (0..).zip(entries) {
+            let opts = CharsetOptions {
+                seed: opts.seed.map(|seed| seed.wrapping_add(i)),
+                ..opts
+            };
+            let old = mem::replace(&mut entry.password, generate_password(len, opts).into());
+            entry.history.push(old);
+        }
+    }
+}
+
+/// The reason [PasswordManagerBuilder::with_accounts_from_json] failed to read its input.
+#[cfg(feature = "json")]
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum JsonError {
+    /// The input isn't valid JSON, or is empty.
+    InvalidJson { line: usize, column: usize },
+    /// The input is valid JSON but isn't an object whose values are all strings, such as an array or an object with an object nested in it.
+    UnexpectedStructure { line: usize, column: usize },
+}
+
+#[cfg(feature = "json")]
+impl fmt::Display for JsonError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            JsonError::InvalidJson { line, column } => {
+                write!(f, "invalid JSON at line {line} column {column}")
+            }
+            JsonError::UnexpectedStructure { line, column } => write!(
+                f,
+                "expected an object of account names to passwords at line {line} column {column}"
+            ),
+        }
+    }
+}
+
+#[cfg(feature = "json")]
+impl core::error::Error for JsonError {}
+
+/// The reason [PasswordManager::from_csv] failed to read its input.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum CsvError {
+    /// A quoted field was never closed.  The line is the one the field started on, counting from 1.
+    UnterminatedQuote { line: usize },
+    /// A quoted field was followed by something other than a comma or the end of the line.
+    UnexpectedCharacter { line: usize, character: char },
+    /// A record didn't have exactly an account and a password in it.
+    WrongFieldCount { line: usize, fields: usize },
+}
+
+impl fmt::Display for CsvError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CsvError::UnterminatedQuote { line } => write!(
+                f,
+                "the quoted field starting on line {line} is never closed"
+            ),
+            CsvError::UnexpectedCharacter { line, character } => {
+                write!(
+                    f,
+                    "unexpected {character:?} after a quoted field on line {line}"
+                )
+            }
+            CsvError::WrongFieldCount { line, fields } => {
+                write!(
+                    f,
+                    "line {line} has {fields} fields instead of an account and a password"
+                )
+            }
+        }
+    }
+}
+
+impl core::error::Error for CsvError {}
+
+/// Append a field to a line of CSV, quoting it if needed.
+fn write_csv_field(csv: &mut String, field: &str) {
+    if field.contains([',', '"', '\n', '\r']) {
+        csv.push('"');
+        csv.push_str(&field.replace('"', "\"\""));
+        csv.push('"');
+    } else {
+        csv.push_str(field);
+    }
+}
+
+/// Split CSV into records of fields, each paired with the line number the record started on.  Blank lines are skipped.
+fn parse_csv(csv: &str) -> Result<Vec<(usize, Vec<String>)>, CsvError> {
+    let mut records = Vec::new();
+    let mut record = Vec::new();
+    let mut field = String::new();
+    // Whether anything has been read for the current record, so blank lines can be told apart from a record with one empty field.
+    let mut started = false;
+    let mut line = 1;
+    let mut record_line = 1;
+    let mut chars = csv.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if field.is_empty() => {
+                started = true;
+                let quote_line = line;
+                loop {
+                    match chars.next() {
+                        Some('"') if chars.peek() == Some(&'"') => {
+                            chars.next();
+                            field.push('"');
+                        }
+                        Some('"') => break,
+                        Some(c) => {
+                            if c == '\n' {
+                                line += 1;
+                            }
+                            field.push(c);
+                        }
+                        None => return Err(CsvError::UnterminatedQuote { line: quote_line }),
+                    }
+                }
+                match chars.peek().copied() {
+                    None | Some(',' | '\n') => {}
+                    // A carriage return only ends the field as part of a `\r\n` line ending.
+                    Some('\r') if chars.clone().nth(1) == Some('\n') => {}
+                    Some(character) => {
+                        return Err(CsvError::UnexpectedCharacter { line, character })
+                    }
+                }
+            }
+            ',' => {
+                started = true;
+                record.push(mem::take(&mut field));
+            }
+            '\r' if chars.peek() == Some(&'\n') => {}
+            '\n' => {
+                if started {
+                    record.push(mem::take(&mut field));
+                    records.push((record_line, mem::take(&mut record)));
+                }
+                started = false;
+                line += 1;
+                record_line = line;
+            }
+            c => {
+                started = true;
+                field.push(c);
+            }
+        }
+    }
+    if started {
+        record.push(field);
+        records.push((record_line, record));
+    }
+    Ok(records)
+}
+
+/// Parse a password manager from a compact text format, which is handy for test fixtures.
+///
+/// The first line is the master password and every line after it is an account in the form `account=password`.  The password is everything after
+/// the first `=`, so it can contain more of them.  Blank lines after the master password are skipped.
+///
+/// ```
+/// use rust_typestate::{Locked, PasswordManager};
+///
+/// let manager = PasswordManager::<Locked>::try_from("Master Password\ntest@example.com=Bees123").unwrap();
+/// assert_eq!(manager.account_count(), 1);
+/// ```
+impl TryFrom<&str> for PasswordManager<Locked> {
+    type Error = ParseError;
+
+    fn try_from(vault: &str) -> Result<Self, ParseError> {
+        let mut lines = vault.lines();
+        let master_password = lines
+            .next()
+            .filter(|line| !line.trim().is_empty())
+            .ok_or(ParseError::MissingMasterPassword)?;
+
+        let mut accounts = Vec::new();
+        // The master password is on line 1, so accounts start on line 2.
+        for (line, text) in (2..).zip(lines) {
+            if text.trim().is_empty() {
+                continue;
+            }
+            match text.split_once('=') {
+                Some((account, password)) if !account.is_empty() => {
+                    accounts.push((account, password))
+                }
+                _ => return Err(ParseError::MalformedAccount { line }),
+            }
+        }
+
+        Ok(PasswordManagerBuilder::new()
+            .with_master_password(master_password)
+            .with_accounts(accounts)
+            .build())
+    }
+}
+
+/// The reason parsing a [PasswordManager] from text with [TryFrom] failed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ParseError {
+    /// The input was empty or its first line, which should be the master password, was blank.
+    MissingMasterPassword,
+    /// An account line wasn't in the form `account=password`.  The line is counted from 1, including the master password's line.
+    MalformedAccount { line: usize },
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseError::MissingMasterPassword => {
+                write!(f, "the first line should be the master password")
+            }
+            ParseError::MalformedAccount { line } => {
+                write!(f, "line {line} should be in the form `account=password`")
+            }
+        }
+    }
+}
+
+impl core::error::Error for ParseError {}
+
+// Indexing is only implemented for unlocked managers, so a locked manager can't be indexed into any more than it can be read from.
+impl<S: SaveState, B: SecretStore<String, SecretString>> core::ops::Index<&str>
+    for PasswordManager<Unlocked<S>, String, SecretString, DefaultHashBuilder, B>
+{
+    type Output = str;
+
+    /// Get the password for an account.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the account isn't stored in the password manager, the same as indexing a [HashMap].
+    fn index(&self, account: &str) -> &str {
+        self.get_password_ref(account)
+            .expect("account not found in password manager")
+    }
+}
+
+// Extending changes accounts in place, so like the other functions that do it is only implemented for managers that already have unsaved changes.
+impl<B: SecretStore<String, SecretString>> Extend<(String, String)>
+    for PasswordManager<Unlocked<Dirty>, String, SecretString, DefaultHashBuilder, B>
+{
+    /// Insert every account and password, replacing the passwords of accounts that already exist and adding the old ones to their histories.
+    fn extend<I: IntoIterator<Item = (String, String)>>(&mut self, accounts: I) {
+        for (account, password) in accounts {
+            self.insert(account, password);
+        }
+    }
+}
+
+/// A guard that locks an unlocked [PasswordManager] as soon as it goes out of scope, including when a panic unwinds through the scope.
+///
+/// The guard dereferences to a [Dirty] unlocked manager so accounts can be changed in place as normal.  When it is dropped, the manager is locked
+/// and put in the slot the guard was created with, so it can be unlocked again afterwards without ever being left unlocked by accident.  Use
+/// [AutoLock::into_inner] to keep the manager unlocked instead.
+///
+/// Locking a manager with unsaved changes is normally refused, but leaving it unlocked would be worse here.  Any changes made through the guard are
+/// kept in the locked manager, encrypted along with its other accounts if the `encryption` feature is enabled, but they aren't saved to a file.  Take
+/// the manager out with [AutoLock::into_inner] and save it with [PasswordManager::save_to_file] to keep them.
+#[derive(Debug)]
+pub struct AutoLock<
+    'a,
+    K: Encrypt = String,
+    V: Wipe + Encrypt = SecretString,
+    H: Default = DefaultHashBuilder,
+> {
+    // This is only `None` once the manager has been taken out by `into_inner` or `drop`.
+    manager: Option<PasswordManager<Unlocked<Dirty>, K, V, H>>,
+    slot: &'a mut Option<PasswordManager<Locked, K, V, H>>,
+}
+
+impl<'a, K: Encrypt, V: Wipe + Encrypt, H: Default> AutoLock<'a, K, V, H> {
+    /// Guard an unlocked manager, putting it in `slot` once it has been locked.
+    pub fn new<S: SaveState>(
+        manager: PasswordManager<Unlocked<S>, K, V, H>,
+        slot: &'a mut Option<PasswordManager<Locked, K, V, H>>,
+    ) -> Self {
+        AutoLock {
+            manager: Some(manager.transition()),
+            slot,
+        }
+    }
+
+    /// Take the manager back out of the guard without locking it.  It is [Dirty] as it may have been changed through the guard.
+    pub fn into_inner(mut self) -> PasswordManager<Unlocked<Dirty>, K, V, H> {
+        self.manager
+            .take()
+            .expect("The guard holds its manager until it is taken out")
+    }
+}
+
+impl<K: Encrypt, V: Wipe + Encrypt, H: Default> core::ops::Deref for AutoLock<'_, K, V, H> {
+    type Target = PasswordManager<Unlocked<Dirty>, K, V, H>;
+
+    fn deref(&self) -> &Self::Target {
+        self.manager
+            .as_ref()
+            .expect("The guard holds its manager until it is taken out")
+    }
+}
+
+impl<K: Encrypt, V: Wipe + Encrypt, H: Default> core::ops::DerefMut for AutoLock<'_, K, V, H> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.manager
+            .as_mut()
+            .expect("The guard holds its manager until it is taken out")
+    }
+}
+
+impl<K: Encrypt, V: Wipe + Encrypt, H: Default> Drop for AutoLock<'_, K, V, H> {
+    fn drop(&mut self) {
+        // The changes are kept rather than lost, as described on the guard itself.
+        if let Some(manager) = self.manager.take() {
+            *self.slot = Some(manager.transition::<Unlocked>().lock());
+        }
+    }
+}
+
+// Moving between states works the same for every state.  Each transition then only has to change the fields it resets.
+impl<State: ManagerState, K, V: Wipe, H, B: AccountList<K, V> + Default>
+    PasswordManager<State, K, V, H, B>
+{
+    /// Move every field of this manager unchanged into a manager of another state.
+    fn transition<T: ManagerState>(mut self) -> PasswordManager<T, K, V, H, B> {
+        let password_list = mem::take(&mut self.password_list);
+        self.transition_with_store(password_list)
+    }
+
+    /// Move every field of this manager except its accounts into a manager of another state, which keeps its accounts in `password_list`.
+    fn transition_with_store<T: ManagerState, C: AccountList<K, V>>(
+        mut self,
+        password_list: C,
+    ) -> PasswordManager<T, K, V, H, C> {
+        // In the future, if RFC 2528 passes, this could be replaced with `PasswordManager { ..self }`.
+        // The fields are taken rather than moved as moving out of a type that implements `Drop` isn't allowed.  This leaves `self` empty so nothing
+        // is zeroed when it is dropped here.
+        PasswordManager {
+            master_password: mem::take(&mut self.master_password),
+            password_list,
+            attempts_remaining: self.attempts_remaining,
+            max_attempts: self.max_attempts,
+            failed_attempts: self.failed_attempts,
+            expires_at: self.expires_at.take(),
+            unlocked_at: self.unlocked_at.take(),
+            normalize_account: self.normalize_account,
+            on_transition: self.on_transition.take(),
+            sealed_accounts: self.sealed_accounts.take(),
+            store_types: PhantomData,
+            state: PhantomData,
+        }
+    }
+}
+
+// Functions only implemented on unlocked password managers, whichever store they keep their accounts in.
+impl<S: SaveState, K, V: Wipe, H, B: AccountList<K, V> + Default>
+    PasswordManager<Unlocked<S>, K, V, H, B>
+{
+    /// Move every account into another [SecretStore], such as a [BTreeMap](alloc::collections::BTreeMap) to always list them in the same order.
+    ///
+    /// The manager keeps its master password and settings, and is looked up in, changed, locked and unlocked the same as before.
+    pub fn with_store<C: SecretStore<K, V> + Default>(
+        mut self,
+    ) -> PasswordManager<Unlocked<S>, K, V, H, C> {
+        let mut store = C::default();
+        for (account, entry) in self.password_list.drain() {
+            store.insert(account, entry);
+        }
+        self.transition_with_store(store)
+    }
+}
+
+// Locking works the same from every state that can be locked.
+impl<
+        State: Readable + ManagerState,
+        K: Encrypt,
+        V: Wipe + Encrypt,
+        H,
+        B: AccountList<K, V> + Default,
+    > PasswordManager<State, K, V, H, B>
+{
+    fn into_locked(mut self) -> PasswordManager<Locked, K, V, H, B> {
+        self.sealed_accounts = seal(&mut self.master_password, &mut self.password_list);
+        let mut locked = self.transition::<Locked>();
+        locked.attempts_remaining = locked.max_attempts;
+        locked.failed_attempts = 0;
+        locked.expires_at = None;
+        locked.unlocked_at = None;
+        locked.notify(TransitionEvent::Locked);
+        locked
+    }
+}
+
+// Displaying a manager only shows its state and how many accounts it holds, so it is safe to log.  The account names are left out as well as the
+// passwords so usernames don't end up in logs either.
+impl<K, V: Wipe, H, B: AccountList<K, V>> fmt::Display for PasswordManager<Locked, K, V, H, B> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "PasswordManager(locked, {})",
+            AccountCount(self.account_count())
+        )
+    }
+}
+
+impl<S, K, V: Wipe, H, B: AccountList<K, V>> fmt::Display
+    for PasswordManager<Unlocked<S>, K, V, H, B>
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "PasswordManager(unlocked, {})",
+            AccountCount(self.password_list.len())
+        )
+    }
+}
+
+impl<K, V: Wipe, H, B: AccountList<K, V>> fmt::Display for PasswordManager<ReadOnly, K, V, H, B> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "PasswordManager(read-only, {})",
+            AccountCount(self.password_list.len())
+        )
+    }
+}
+
+impl<K, V: Wipe, H, B: AccountList<K, V>> fmt::Display for PasswordManager<Frozen, K, V, H, B> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "PasswordManager(frozen, {})",
+            AccountCount(self.password_list.len())
+        )
+    }
+}
+
+// Debug output ends up in panic messages and logs, so it is written by hand to redact the secrets that a derived implementation would print.
+impl<State, K, V: Wipe, H, B: AccountList<K, V>> fmt::Debug for PasswordManager<State, K, V, H, B> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PasswordManager")
+            .field("master_password", &"<redacted>")
+            .field(
+                "password_list",
+                &format_args!("{{<{} entries>}}", self.count_accounts()),
+            )
+            .field("attempts_remaining", &self.attempts_remaining)
+            .field("max_attempts", &self.max_attempts)
+            .field("failed_attempts", &self.failed_attempts)
+            .field("state", &format_args!("{}", state_name::<State>()))
+            .finish()
+    }
+}
+
+impl<State, K, V: Wipe, H, B: AccountList<K, V>> PasswordManager<State, K, V, H, B> {
+    /// Count the accounts in this manager, including any that are encrypted.
+    fn count_accounts(&self) -> usize {
+        self.password_list.len() + self.sealed_accounts.as_ref().map_or(0, SealedAccounts::len)
+    }
+
+    /// Tell the hook registered with [PasswordManagerBuilder::on_transition], if there is one, that this manager has changed state.
+    fn notify(&self, event: TransitionEvent) {
+        if let Some(on_transition) = &self.on_transition {
+            on_transition(event);
+        }
+    }
+}
+
+/// Get the name of a state type without its module path, such as "Locked".
+fn state_name<State>() -> &'static str {
+    // Generic states like `Unlocked<Saved>` are named without their parameters, which have module paths of their own.
+    let name = core::any::type_name::<State>();
+    let name = name.split('<').next().unwrap_or(name);
+    name.rsplit("::").next().unwrap_or(name)
+}
+
+/// Displays a number of accounts, such as "1 account" or "3 accounts".
+struct AccountCount(usize);
+
+impl fmt::Display for AccountCount {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.0 {
+            1 => write!(f, "1 account"),
+            count => write!(f, "{count} accounts"),
+        }
+    }
+}
+
+// Wipe the secrets before their memory is freed.  Transitioning between states takes the fields out of the old manager, so this only zeroes anything
+// when the secrets themselves are being dropped.
+#[cfg(feature = "zeroize")]
+impl<State, K, V: Wipe, H, B: AccountList<K, V>> Drop for PasswordManager<State, K, V, H, B> {
+    fn drop(&mut self) {
+        use zeroize::Zeroize;
+
+        self.master_password.zeroize();
+        self.password_list
+            .iter_mut()
+            .for_each(|(_, entry)| entry.wipe());
+    }
+}
+
+/// Denotes that a [PasswordManagerBuilder] hasn't had its master password set yet, or that an [AccountBuilder] hasn't had its password set.
+#[derive(Debug)]
+pub struct MissingPassword;
+/// Denotes that a [PasswordManagerBuilder] hasn't had its master password set yet, but that the master password must be at least this many
+/// characters long when it is.  See [PasswordManagerBuilder::with_min_master_length].
 #[derive(Debug)]
-pub struct Unlocked;
+pub struct MinMasterLength(usize);
+/// Denotes that a [PasswordManagerBuilder] has had its master password set.
+///
+/// With the `hashing` feature, the master password is hashed as soon as it is set, but the plaintext is also kept until the manager is built so it
+/// can be checked against a minimum strength or a [PasswordPolicy].  A master password taken from an existing manager where only the hash is left
+/// can't be checked.
+pub struct MasterPassword(MasterKey, Option<SecretString>);
 
-/// The password manager struct.
+impl fmt::Debug for MasterPassword {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("MasterPassword")
+            .field(&"<redacted>")
+            .finish()
+    }
+}
+
+/// The master password state of a [PasswordManagerBuilder], which may or may not have a master password in it.
 ///
-/// Instead of embedding the locked state using a boolean field on the struct, it is implemented as a generic type.
-/// This has the benefit of there being no extra memory usage incurred by storing the state as a field within the struct itself, but the amount of memory
-/// taken by a single boolean value is negligable in most cases.
+/// This lets [PasswordManagerBuilder::try_build] be called on a builder without knowing at compile time whether its master password has been set.
+pub trait MaybeMasterPassword {
+    /// Take the master password out of this state, if it has one.
+    fn into_master_password(self) -> Option<MasterPassword>;
+}
+
+impl MaybeMasterPassword for MissingPassword {
+    fn into_master_password(self) -> Option<MasterPassword> {
+        None
+    }
+}
+
+impl MaybeMasterPassword for MinMasterLength {
+    fn into_master_password(self) -> Option<MasterPassword> {
+        None
+    }
+}
+
+impl MaybeMasterPassword for MasterPassword {
+    fn into_master_password(self) -> Option<MasterPassword> {
+        Some(self)
+    }
+}
+
+/// The reason [PasswordManager::try_insert] refused to insert an account.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum InsertError {
+    /// The account name was empty or only whitespace, so it couldn't be told apart from no account at all.
+    EmptyAccount,
+}
+
+impl fmt::Display for InsertError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            InsertError::EmptyAccount => write!(f, "the account name is empty"),
+        }
+    }
+}
+
+impl core::error::Error for InsertError {}
+
+/// The reason a [PasswordManagerBuilder] failed to build a [PasswordManager].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum BuildError {
+    /// The builder never had its master password set.
+    MissingMasterPassword,
+}
+
+impl fmt::Display for BuildError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BuildError::MissingMasterPassword => write!(f, "the master password hasn't been set"),
+        }
+    }
+}
+
+impl core::error::Error for BuildError {}
+
+/// How hard a master password is to guess, as judged by [evaluate_strength].
 ///
-/// The main reason for this choice is to turn some runtime errors into compile time errors.  Instead of each method implemented for this struct having to
-/// check the manager's locked state and reacting to it (for example a .get_password(..) method having to return a [Result] in case the manager is locked),
-/// methods are only implemented on the struct for the states they apply to.  This simplifies the return type of the methods, reduces code duplication, and
-/// only exposes an API of methods that are valid to call given the current state.
+/// The variants are ordered from weakest to strongest so they can be compared against a minimum.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum PasswordStrength {
+    /// Shorter than 8 characters or made up of only one kind of character.
+    Weak,
+    /// At least 8 characters using at least two kinds of character.
+    Medium,
+    /// At least 12 characters using at least three kinds of character.
+    Strong,
+}
+
+/// Judge the strength of a password based on its length and how many kinds of character it uses.
 ///
-/// The fields all being private also prevents accidentally leaking passwords from locked managers by just reading them.  The only safe way the API allows
-/// password retrieval is by getting them from an unlocked manager.
+/// The kinds of character are lowercase letters, uppercase letters, digits, and everything else.  This is only a rough guide and doesn't check for
+/// common passwords or dictionary words.
+pub fn evaluate_strength(password: &str) -> PasswordStrength {
+    let length = password.chars().count();
+    let classes = [
+        password.chars().any(char::is_lowercase),
+        password.chars().any(char::is_uppercase),
+        password.chars().any(|c| c.is_ascii_digit()),
+        password.chars().any(|c| !c.is_alphanumeric()),
+    ]
+    .into_iter()
+    .filter(|&present| present)
+    .count();
+
+    match (length, classes) {
+        (12.., 3..) => PasswordStrength::Strong,
+        (8.., 2..) => PasswordStrength::Medium,
+        _ => PasswordStrength::Weak,
+    }
+}
+
+/// Estimate how many bits of entropy a password has as `length * log2(pool)`, where the pool is the number of characters it could have been drawn
+/// from.
 ///
-/// This could be rewritten to have a generic identifier and account information type but for the purposes of this demonstration a
-/// [HashMap<String, String>] of account usernames to passwords is used.
-#[derive(Debug)]
-pub struct PasswordManager<State = Locked> {
-    master_password: String,
-    password_list: HashMap<String, String>,
-    state: PhantomData<State>,
+/// Each kind of character the password uses adds to the pool: 26 for lowercase letters, 26 for uppercase letters, 10 for digits and 33 for
+/// everything else, which is the number of ASCII symbols including space.  This assumes every character was picked at random, so it overestimates
+/// passwords made of words or patterns.  An empty password has no entropy.
+#[cfg(feature = "std")]
+fn estimate_entropy(password: &str) -> f64 {
+    let pool: u32 = [
+        (password.chars().any(char::is_lowercase), 26),
+        (password.chars().any(char::is_uppercase), 26),
+        (password.chars().any(|c| c.is_ascii_digit()), 10),
+        (password.chars().any(|c| !c.is_alphanumeric()), 33),
+    ]
+    .into_iter()
+    .filter_map(|(present, size)| present.then_some(size))
+    .sum();
+    if pool == 0 {
+        return 0.0;
+    }
+    password.chars().count() as f64 * f64::from(pool).log2()
 }
 
-impl PasswordManager<Locked> {
-    /// Attempt to unlock a password manager using the master password.
-    ///
-    /// Because the locked and unlocked managers are technically different types, this method has to return a
-    /// [Result<PasswordManager\<Unlocked>, PasswordManager\<Locked>>].  This has a few benefits:
-    /// - It forces the API user to handle the case of an invalid password being entered.
-    /// - Since this function moves the password manager, the Err variant gives back the original locked password manager in case of the wrong password.
-    pub fn unlock(
-        self,
-        master_password: impl Into<String>,
-    ) -> Result<PasswordManager<Unlocked>, PasswordManager<Locked>> {
-        // Accepting an `impl Into<String>` is more flexible for the API caller than just `String` or `&str`.
-        let password = master_password.into();
-        match password == self.master_password {
-            // In the future, if RFC 2528 passes, this could be replaced with `true => Ok(PasswordManager { ..self }),`.
-            true => Ok(PasswordManager {
-                master_password: self.master_password,
-                password_list: self.password_list,
-                state: PhantomData,
-            }),
-            false => Err(self),
-        }
+/// The error returned when a [PasswordManagerBuilder]'s master password is weaker than required.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WeakPasswordError {
+    /// The strength the master password was evaluated at.
+    pub strength: PasswordStrength,
+}
+
+impl fmt::Display for WeakPasswordError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "the master password is too weak, its strength is only {:?}",
+            self.strength
+        )
     }
 }
 
-// Functions only implemented on unlocked password managers.
-impl PasswordManager<Unlocked> {
-    /// Lock this password manager so that the master password is required to unlock it again.
-    pub fn lock(self) -> PasswordManager<Locked> {
-        PasswordManager {
-            master_password: self.master_password,
-            password_list: self.password_list,
-            state: PhantomData,
+impl core::error::Error for WeakPasswordError {}
+
+/// The error returned when a [PasswordManagerBuilder]'s master password is on a list of common passwords.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CommonPasswordError;
+
+impl fmt::Display for CommonPasswordError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "the master password is a common password")
+    }
+}
+
+impl core::error::Error for CommonPasswordError {}
+
+/// The error returned by [PasswordManagerBuilder::with_account_checked] when the account has already been added to the builder.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DuplicateAccountError;
+
+impl fmt::Display for DuplicateAccountError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "the account has already been added")
+    }
+}
+
+impl core::error::Error for DuplicateAccountError {}
+
+/// The error returned by [PasswordManagerBuilder::validate_no_case_collisions] when accounts differ only by case.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CaseCollisionError {
+    /// Each group of accounts that are the same ignoring case, sorted within each group and by the groups' first accounts.
+    pub collisions: Vec<Vec<String>>,
+}
+
+impl fmt::Display for CaseCollisionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "some accounts differ only by case:")?;
+        for accounts in &self.collisions {
+            write!(f, " {accounts:?}")?;
         }
+        Ok(())
     }
+}
 
-    /// Get a list of the stored accounts and their passwords.
-    pub fn get_passwords(&self) -> HashMap<String, String> {
-        self.password_list.clone()
+impl core::error::Error for CaseCollisionError {}
+
+/// The error returned by [PasswordManagerBuilder::with_master_password] when the master password is shorter than the minimum length given to
+/// [PasswordManagerBuilder::with_min_master_length].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TooShortError {
+    /// The minimum length in characters.
+    pub min: usize,
+    /// The length of the rejected master password in characters.
+    pub len: usize,
+}
+
+impl fmt::Display for TooShortError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "the master password is {} characters long but must be at least {}",
+            self.len, self.min
+        )
     }
+}
 
-    /// Get a single password given the account.
-    pub fn get_password(&self, account: &str) -> Option<String> {
-        self.password_list.get(account).map(|s| s.to_owned())
+impl core::error::Error for TooShortError {}
+
+/// A rule a master password has to follow, checked by [PasswordManagerBuilder::try_build_with_policy].
+///
+/// This lets callers enforce their own requirements where the fixed levels of [PasswordStrength] don't fit.
+pub trait PasswordPolicy {
+    /// Check `pw` against the policy, returning an error describing why it was rejected if it doesn't follow it.
+    fn validate(&self, pw: &str) -> Result<(), PolicyError>;
+}
+
+/// A [PasswordPolicy] requiring passwords to be at least this many characters long.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MinLengthPolicy(pub usize);
+
+impl PasswordPolicy for MinLengthPolicy {
+    fn validate(&self, pw: &str) -> Result<(), PolicyError> {
+        match pw.chars().count() {
+            length if length < self.0 => Err(PolicyError::new(alloc::format!(
+                "the password is {length} characters long but must be at least {}",
+                self.0
+            ))),
+            _ => Ok(()),
+        }
     }
+}
+
+/// The error returned when a [PasswordPolicy] rejects a password.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PolicyError {
+    /// Why the password was rejected.
+    pub reason: String,
+}
 
-    /// Insert a new account and password into the password manager.
-    pub fn insert(&mut self, account: impl Into<String>, password: impl Into<String>) {
-        self.password_list.insert(account.into(), password.into());
+impl fmt::Display for PolicyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "the password was rejected by the policy: {}",
+            self.reason
+        )
     }
 }
 
-/// Denotes that a [PasswordManagerBuilder] hasn't had its master password set yet.
-pub struct MissingPassword;
-/// Denotes that a [PasswordManagerBuilder] has had its master password set.
-pub struct MasterPassword(String);
+impl core::error::Error for PolicyError {}
+
+impl PolicyError {
+    /// Create an error rejecting a password for `reason`.
+    pub fn new(reason: impl Into<String>) -> Self {
+        PolicyError {
+            reason: reason.into(),
+        }
+    }
+}
 
 /// A struct for implementing the builder pattern for the [PasswordManager].
-pub struct PasswordManagerBuilder<P = MissingPassword> {
+///
+/// The account and password types default to [String] and [SecretString].  Builders for other types can be created with [Default::default] and a type annotation on
+/// the built manager.
+pub struct PasswordManagerBuilder<
+    P = MissingPassword,
+    K = String,
+    V = SecretString,
+    H = DefaultHashBuilder,
+> {
     master_password: P,
-    password_list: HashMap<String, String>,
+    password_list: HashMap<K, AccountEntry<V>, H>,
+    max_attempts: Option<u8>,
+    normalize_account: Option<NormalizeAccount<K>>,
+    on_transition: Option<TransitionHook>,
 }
 
 impl PasswordManagerBuilder {
     /// Create a new password manager builder with no master password and an empty account list.
     pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create a new password manager builder with no master password and an empty account list with room for at least `capacity` accounts.
+    ///
+    /// This avoids growing the account list over and over when the number of accounts that will be added is known up front.
+    pub fn with_capacity(capacity: usize) -> Self {
         PasswordManagerBuilder {
-            master_password: MissingPassword,
-            password_list: HashMap::new(),
+            password_list: HashMap::with_capacity(capacity),
+            ..Self::default()
         }
     }
 }
 
-impl Default for PasswordManagerBuilder {
+impl<K, V, H: Default> Default for PasswordManagerBuilder<MissingPassword, K, V, H> {
     fn default() -> Self {
-        Self::new()
+        PasswordManagerBuilder {
+            master_password: MissingPassword,
+            password_list: HashMap::default(),
+            max_attempts: None,
+            normalize_account: None,
+            on_transition: None,
+        }
     }
 }
 
-// Implement `with_account(..)` for password manager builders irrespective of whether the master password is set or not.
-impl<P> PasswordManagerBuilder<P> {
+// Implement `with_account(..)` and `with_accounts(..)` for password manager builders irrespective of whether the master password is set or not.
+impl<P, K: Eq + Hash, V, H: BuildHasher> PasswordManagerBuilder<P, K, V, H> {
     /// Add an account and password to the password manager.
-    pub fn with_account(self, account: impl Into<String>, password: impl Into<String>) -> Self {
-        let mut new_password_list = self.password_list.clone();
-        new_password_list.insert(account.into(), password.into());
+    pub fn with_account(self, account: impl Into<K>, password: impl Into<V>) -> Self {
+        self.with_account_entry(account, AccountEntry::new(password))
+    }
+
+    /// Add an account and password to the password manager only if `cond` is true, so optional accounts don't break up a chain of calls.
+    pub fn with_account_if(
+        self,
+        cond: bool,
+        account: impl Into<K>,
+        password: impl Into<V>,
+    ) -> Self {
+        if cond {
+            self.with_account(account, password)
+        } else {
+            self
+        }
+    }
+
+    /// Add an account and password to the password manager, or return a [DuplicateAccountError] if the account has already been added.
+    ///
+    /// Unlike [PasswordManagerBuilder::with_account], this catches an account accidentally given twice in a long chain of calls rather than
+    /// letting the last password silently win.
+    pub fn with_account_checked(
+        mut self,
+        account: impl Into<K>,
+        password: impl Into<V>,
+    ) -> Result<Self, DuplicateAccountError> {
+        let (account, name) = normalize(self.normalize_account, account.into());
+        if self.password_list.contains_key(&account) {
+            return Err(DuplicateAccountError);
+        }
+        self.password_list
+            .insert(account, AccountEntry::new(password).named(name));
+        Ok(self)
+    }
+
+    /// Add an account to the password manager, configuring its password, URL, notes and tags with an [AccountBuilder] in `f`.
+    ///
+    /// ```
+    /// use rust_typestate::PasswordManagerBuilder;
+    ///
+    /// let builder = PasswordManagerBuilder::new().with_account_using("test@example.com", |account| {
+    ///     account.password("Bees123").url("https://example.com").tag("work")
+    /// });
+    /// ```
+    pub fn with_account_using<F>(self, account: impl Into<K>, f: F) -> Self
+    where
+        F: FnOnce(AccountBuilder) -> AccountBuilder<V>,
+    {
+        self.with_account_entry(account, f(AccountBuilder::new()).build())
+    }
+
+    /// Add an account to the password manager along with its URL, notes and tags.
+    pub fn with_account_entry(mut self, account: impl Into<K>, entry: AccountEntry<V>) -> Self {
+        // The builder is taken by value so the existing list can be inserted into and moved along rather than cloned on every call.
+        let (account, name) = normalize(self.normalize_account, account.into());
+        self.password_list.insert(account, entry.named(name));
+        self
+    }
+
+    /// Add every account and password from an iterator to the password manager.
+    ///
+    /// Like [HashMap::extend], if an account appears more than once the last password given for it wins.
+    pub fn with_accounts<I, A, B>(mut self, accounts: I) -> Self
+    where
+        I: IntoIterator<Item = (A, B)>,
+        A: Into<K>,
+        B: Into<V>,
+    {
+        let normalize_account = self.normalize_account;
+        self.password_list
+            .extend(accounts.into_iter().map(|(account, password)| {
+                let (account, name) = normalize(normalize_account, account.into());
+                (account, AccountEntry::new(password).named(name))
+            }));
+        self
+    }
+
+    /// Add an account to the password manager with a freshly generated random password.
+    ///
+    /// See [generate_password] for how the password is generated.
+    #[cfg(feature = "std")]
+    pub fn with_generated_account(
+        self,
+        account: impl Into<K>,
+        len: usize,
+        opts: CharsetOptions,
+    ) -> Self
+    where
+        V: From<String>,
+    {
+        self.with_account(account, generate_password(len, opts))
+    }
+
+    /// Add the accounts and passwords from a JSON object of account names to passwords, such as `{"test@example.com": "Bees123"}`.
+    ///
+    /// Like [PasswordManagerBuilder::with_accounts], if an account is already in the builder or appears more than once, the last password given
+    /// for it wins.  Input that isn't valid JSON, including empty input, or that isn't an object of strings gives an error instead.
+    #[cfg(feature = "json")]
+    pub fn with_accounts_from_json(self, json: &str) -> Result<Self, JsonError>
+    where
+        K: From<String>,
+        V: From<String>,
+    {
+        let accounts: alloc::collections::BTreeMap<String, String> = serde_json::from_str(json)
+            .map_err(|error| {
+                let (line, column) = (error.line(), error.column());
+                match error.classify() {
+                    serde_json::error::Category::Data => {
+                        JsonError::UnexpectedStructure { line, column }
+                    }
+                    _ => JsonError::InvalidJson { line, column },
+                }
+            })?;
+        Ok(self.with_accounts(accounts))
+    }
+
+    /// Make the built password manager store its accounts in a map that uses the hasher `G` instead of the default one.
+    ///
+    /// The hasher is created with [Default], so a [BuildHasherDefault](core::hash::BuildHasherDefault) can be used to pick a [Hasher](core::hash::Hasher)
+    /// type.  Any accounts already added to this builder are moved into the new map.
+    pub fn with_hasher<G: BuildHasher + Default>(self) -> PasswordManagerBuilder<P, K, V, G> {
+        PasswordManagerBuilder {
+            master_password: self.master_password,
+            password_list: self.password_list.into_iter().collect(),
+            max_attempts: self.max_attempts,
+            normalize_account: self.normalize_account,
+            on_transition: self.on_transition,
+        }
+    }
+}
+
+// Case-insensitivity can only be turned on for builders with `String` accounts as they are the only ones that can be lowercased.
+impl<P, V, H: BuildHasher + Default> PasswordManagerBuilder<P, String, V, H> {
+    /// Make the built password manager match account names case-insensitively, so `Test@Example.com` and `test@example.com` are the same account.
+    ///
+    /// Accounts are looked up by their lowercased names, including any accounts already added to this builder, but are still listed with the
+    /// name they were first given.  Functions that hand back the keys themselves, such as [PasswordManager::get_passwords], give the lowercased
+    /// names.  This setting isn't kept when a manager is serialized, so a deserialized manager matches its lowercased account names
+    /// case-sensitively.
+    pub fn with_case_insensitive_accounts(mut self) -> Self {
+        let lowercase: NormalizeAccount<String> = |account| {
+            let key = account.to_lowercase();
+            match key == account {
+                true => (key, None),
+                false => (key, Some(account)),
+            }
+        };
+        self.password_list = mem::take(&mut self.password_list)
+            .into_iter()
+            .map(|(account, entry)| {
+                let (account, name) = lowercase(account);
+                (account, entry.named(name))
+            })
+            .collect();
+        self.normalize_account = Some(lowercase);
+        self
+    }
+
+    /// Check that no two accounts in this builder differ only by case, such as `Foo` and `foo`, returning the ones that do as an error.
+    ///
+    /// [PasswordManagerBuilder::with_case_insensitive_accounts] silently keeps just one of such accounts, so call this first to catch them.
+    pub fn validate_no_case_collisions(self) -> Result<Self, CaseCollisionError> {
+        // A sorted map keeps the reported collisions in the same order every time.
+        let mut by_lowercase: alloc::collections::BTreeMap<String, Vec<String>> =
+            Default::default();
+        for (account, entry) in &self.password_list {
+            let account = entry.name_or(account);
+            by_lowercase
+                .entry(account.to_lowercase())
+                .or_default()
+                .push(account.to_string());
+        }
+        let collisions: Vec<Vec<String>> = by_lowercase
+            .into_values()
+            .filter(|accounts| accounts.len() > 1)
+            .map(|mut accounts| {
+                accounts.sort_unstable();
+                accounts
+            })
+            .collect();
+        if collisions.is_empty() {
+            Ok(self)
+        } else {
+            Err(CaseCollisionError { collisions })
+        }
+    }
+}
+
+// Implement `with_max_attempts(..)` and `on_transition(..)` for password manager builders irrespective of their types.
+impl<P, K, V, H> PasswordManagerBuilder<P, K, V, H> {
+    /// Limit the number of wrong master passwords the built password manager will accept before it locks out for good.  By default there is no limit.
+    pub fn with_max_attempts(self, max_attempts: u8) -> Self {
+        Self {
+            max_attempts: Some(max_attempts),
+            ..self
+        }
+    }
+
+    /// Register a hook called every time the built password manager is locked or unlocked, such as to keep an audit log.
+    ///
+    /// The hook is only told which state the manager moved to, never anything secret.  It is kept by clones of the manager but not by serialized
+    /// or saved ones.  Registering another hook replaces this one.
+    pub fn on_transition(self, f: impl Fn(TransitionEvent) + Send + Sync + 'static) -> Self {
         Self {
-            password_list: new_password_list,
+            on_transition: Some(Arc::new(f)),
             ..self
         }
     }
@@ -130,28 +4100,191 @@ impl<P> PasswordManagerBuilder<P> {
 
 // Implement `.with_master_password(..)` only for builders where the master password hasn't been set yet.
 // This could be implemented over generic P to be callable multiple times but it only needs to be set once.
-impl PasswordManagerBuilder<MissingPassword> {
+impl<K, V, H> PasswordManagerBuilder<MissingPassword, K, V, H> {
     /// Set the master password field for this password manager.  If this method is not called on a [PasswordManagerBuilder], the `.build()` method cannot
     /// be called as this would result in an invalid (un-unlockable) password manager.
     pub fn with_master_password(
         self,
         master_password: impl Into<String>,
-    ) -> PasswordManagerBuilder<MasterPassword> {
+    ) -> PasswordManagerBuilder<MasterPassword, K, V, H> {
+        let master_password = master_password.into();
+        let plaintext = SecretString::from(master_password.as_str());
+        PasswordManagerBuilder {
+            master_password: MasterPassword(MasterKey::new(master_password), Some(plaintext)),
+            password_list: self.password_list,
+            max_attempts: self.max_attempts,
+            normalize_account: self.normalize_account,
+            on_transition: self.on_transition,
+        }
+    }
+
+    /// Require the master password to be at least `min` characters long, so [PasswordManagerBuilder::with_master_password] returns a
+    /// [TooShortError] instead of accepting a shorter one.
+    ///
+    /// This is a lighter alternative to [PasswordManagerBuilder::try_build_with_policy] for when only the length matters.
+    pub fn with_min_master_length(
+        self,
+        min: usize,
+    ) -> PasswordManagerBuilder<MinMasterLength, K, V, H> {
         PasswordManagerBuilder {
-            master_password: MasterPassword(master_password.into()),
+            master_password: MinMasterLength(min),
+            password_list: self.password_list,
+            max_attempts: self.max_attempts,
+            normalize_account: self.normalize_account,
+            on_transition: self.on_transition,
+        }
+    }
+
+    /// Set the master password for this password manager from the environment variable `var`, as command line tools often do.
+    ///
+    /// Returns the error from [std::env::var] if the variable isn't set or isn't valid unicode.
+    #[cfg(feature = "std")]
+    pub fn with_master_password_from_env(
+        self,
+        var: &str,
+    ) -> Result<PasswordManagerBuilder<MasterPassword, K, V, H>, VarError> {
+        std::env::var(var).map(|master_password| self.with_master_password(master_password))
+    }
+}
+
+// Builders with a minimum master password length check the master password as soon as it is set.
+impl<K, V, H> PasswordManagerBuilder<MinMasterLength, K, V, H> {
+    /// Set the master password field for this password manager, or return a [TooShortError] if it is shorter than the minimum length given to
+    /// [PasswordManagerBuilder::with_min_master_length].  The length is counted in characters rather than bytes.
+    pub fn with_master_password(
+        self,
+        master_password: impl Into<String>,
+    ) -> Result<PasswordManagerBuilder<MasterPassword, K, V, H>, TooShortError> {
+        let master_password = master_password.into();
+        let MinMasterLength(min) = self.master_password;
+        let len = master_password.chars().count();
+        if len < min {
+            return Err(TooShortError { min, len });
+        }
+        let builder = PasswordManagerBuilder {
+            master_password: MissingPassword,
             password_list: self.password_list,
+            max_attempts: self.max_attempts,
+            normalize_account: self.normalize_account,
+            on_transition: self.on_transition,
+        };
+        Ok(builder.with_master_password(master_password))
+    }
+}
+
+// Checks of the master password are only implemented for builders that have one.
+impl<K, V, H> PasswordManagerBuilder<MasterPassword, K, V, H> {
+    /// Check that the master password isn't one of the common passwords in `blocklist`, returning an error if it is.
+    ///
+    /// The master password has to match an entry exactly to be rejected.  Like [PasswordManagerBuilder::try_build_with_min_strength], a master
+    /// password carried over from a hashed manager can't be checked, so it is always accepted.
+    pub fn reject_common_passwords<S: BuildHasher>(
+        self,
+        blocklist: &HashSet<String, S>,
+    ) -> Result<Self, CommonPasswordError> {
+        match &self.master_password.1 {
+            Some(plaintext) if blocklist.contains(plaintext.expose()) => Err(CommonPasswordError),
+            _ => Ok(self),
         }
     }
 }
 
 // Implement `.build(..)` only for builders of the MasterPassword type because valid password managers must have a master password set.
-impl PasswordManagerBuilder<MasterPassword> {
+impl<K: Encrypt, V: Wipe + Encrypt, H: Default> PasswordManagerBuilder<MasterPassword, K, V, H> {
     /// Build a [PasswordManager] from this builder.
-    pub fn build(self) -> PasswordManager {
+    pub fn build(self) -> PasswordManager<Locked, K, V, H> {
+        let MasterPassword(mut master_password, plaintext) = self.master_password;
+        #[cfg(feature = "zeroize")]
+        if let Some(mut plaintext) = plaintext {
+            zeroize::Zeroize::zeroize(&mut plaintext);
+        }
+        let mut password_list = self.password_list;
+        let sealed_accounts = seal(&mut master_password, &mut password_list);
         PasswordManager {
-            master_password: self.master_password.0,
-            password_list: self.password_list,
+            master_password,
+            password_list,
+            attempts_remaining: self.max_attempts,
+            max_attempts: self.max_attempts,
+            failed_attempts: 0,
+            expires_at: None,
+            unlocked_at: None,
+            normalize_account: self.normalize_account,
+            on_transition: self.on_transition,
+            sealed_accounts,
+            store_types: PhantomData,
             state: PhantomData,
         }
     }
+
+    /// Build a [PasswordManager] from this builder, or return an error if its master password is weaker than `min`.
+    ///
+    /// With the `hashing` feature, a master password carried over by [PasswordManagerBuilder::from_unlocked] can't be evaluated as only its hash is
+    /// kept, so it is always accepted.
+    pub fn try_build_with_min_strength(
+        self,
+        min: PasswordStrength,
+    ) -> Result<PasswordManager<Locked, K, V, H>, WeakPasswordError> {
+        let strength = self
+            .master_password
+            .1
+            .as_ref()
+            .map(|plaintext| evaluate_strength(plaintext.expose()));
+        match strength {
+            Some(strength) if strength < min => Err(WeakPasswordError { strength }),
+            _ => Ok(self.build()),
+        }
+    }
+
+    /// Build a [PasswordManager] from this builder, or return the error from `policy` if it rejects the master password.
+    ///
+    /// Like [PasswordManagerBuilder::try_build_with_min_strength], a master password carried over from a hashed manager can't be checked, so it is
+    /// always accepted.
+    pub fn try_build_with_policy(
+        self,
+        policy: &impl PasswordPolicy,
+    ) -> Result<PasswordManager<Locked, K, V, H>, PolicyError> {
+        if let Some(plaintext) = &self.master_password.1 {
+            policy.validate(plaintext.expose())?;
+        }
+        Ok(self.build())
+    }
+
+    /// Create a builder from an unlocked password manager, keeping its master password, accounts and settings, so more accounts can be added
+    /// before building it again.
+    pub fn from_unlocked(mut manager: PasswordManager<Unlocked, K, V, H>) -> Self {
+        // The fields are taken rather than moved out as the manager may implement `Drop`.
+        let master_password = mem::take(&mut manager.master_password);
+        let plaintext = master_password.plaintext().map(SecretString::from);
+        PasswordManagerBuilder {
+            master_password: MasterPassword(master_password, plaintext),
+            password_list: mem::take(&mut manager.password_list),
+            max_attempts: manager.max_attempts,
+            normalize_account: manager.normalize_account,
+            on_transition: manager.on_transition.take(),
+        }
+    }
+}
+
+// Implement `.try_build(..)` for every builder, checking for the master password at runtime instead.
+impl<P: MaybeMasterPassword, K: Encrypt, V: Wipe + Encrypt, H: Default>
+    PasswordManagerBuilder<P, K, V, H>
+{
+    /// Build a [PasswordManager] from this builder, or return an error if its master password hasn't been set.
+    ///
+    /// Prefer `.build()` where possible as it checks for the master password at compile time.  This is for builders that are put together
+    /// dynamically (for example, from a config file) where the compiler can't know whether a master password will be set.
+    pub fn try_build(self) -> Result<PasswordManager<Locked, K, V, H>, BuildError> {
+        let master_password = self
+            .master_password
+            .into_master_password()
+            .ok_or(BuildError::MissingMasterPassword)?;
+        let builder = PasswordManagerBuilder {
+            master_password,
+            password_list: self.password_list,
+            max_attempts: self.max_attempts,
+            normalize_account: self.normalize_account,
+            on_transition: self.on_transition,
+        };
+        Ok(builder.build())
+    }
 }
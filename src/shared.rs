@@ -0,0 +1,96 @@
+//! A [PasswordManager](crate::PasswordManager) that can be shared between threads.
+//!
+//! The typestate pattern relies on a manager being moved into a new type whenever its state changes, which doesn't work when several threads need to
+//! hold the same manager at once.  [SharedPasswordManager] gives up those compile-time guarantees: whether it is locked is only known at runtime, so
+//! reading a password can fail with [NotUnlocked] where a [PasswordManager\<Unlocked>](crate::PasswordManager) would always succeed.  Prefer passing
+//! a plain manager around where possible and only reach for this when it really has to be shared.
+
+use std::fmt;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use crate::password_manager::{Locked, PasswordManager, SecretString, UnlockError, Unlocked};
+
+/// A password manager that can be cloned and shared between threads, checking whether it is locked at runtime.
+///
+/// Clones share the same manager, so unlocking or locking it through one clone affects every other.  Each method briefly locks a mutex around the
+/// manager, so calls from different threads never see it half way through changing state.
+#[derive(Debug, Clone)]
+pub struct SharedPasswordManager(Arc<Mutex<ManagerInner>>);
+
+/// The manager inside a [SharedPasswordManager], in whichever state it is currently in.
+#[derive(Debug)]
+enum ManagerInner {
+    Locked(PasswordManager<Locked>),
+    Unlocked(PasswordManager<Unlocked>),
+    // Only held while the state is changing.  If changing state panics the mutex is poisoned, so no other call can see this.
+    Changing,
+}
+
+/// The error returned when reading from a [SharedPasswordManager] that is locked.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NotUnlocked;
+
+impl fmt::Display for NotUnlocked {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "the shared password manager is locked")
+    }
+}
+
+impl std::error::Error for NotUnlocked {}
+
+impl SharedPasswordManager {
+    /// Share a locked password manager.
+    pub fn new(manager: PasswordManager<Locked>) -> Self {
+        SharedPasswordManager(Arc::new(Mutex::new(ManagerInner::Locked(manager))))
+    }
+
+    /// Unlock the shared manager using the master password.  Unlocking a manager that is already unlocked does nothing.
+    pub fn unlock(&self, master_password: impl Into<String>) -> Result<(), UnlockError> {
+        let mut inner = self.inner();
+        match std::mem::replace(&mut *inner, ManagerInner::Changing) {
+            ManagerInner::Locked(manager) => match manager.try_unlock(master_password) {
+                Ok(unlocked) => {
+                    *inner = ManagerInner::Unlocked(unlocked);
+                    Ok(())
+                }
+                Err((still_locked, error)) => {
+                    *inner = ManagerInner::Locked(still_locked);
+                    Err(error)
+                }
+            },
+            other => {
+                *inner = other;
+                Ok(())
+            }
+        }
+    }
+
+    /// Lock the shared manager so that the master password is required to unlock it again.  Locking a manager that is already locked does nothing.
+    pub fn lock(&self) {
+        let mut inner = self.inner();
+        *inner = match std::mem::replace(&mut *inner, ManagerInner::Changing) {
+            ManagerInner::Unlocked(manager) => ManagerInner::Locked(manager.lock()),
+            other => other,
+        };
+    }
+
+    /// Check whether the shared manager is currently locked.
+    pub fn is_locked(&self) -> bool {
+        !matches!(*self.inner(), ManagerInner::Unlocked(_))
+    }
+
+    /// Get a single password given the account, or a [NotUnlocked] error if the shared manager is locked.
+    pub fn get_password(&self, account: &str) -> Result<Option<SecretString>, NotUnlocked> {
+        match &*self.inner() {
+            ManagerInner::Unlocked(manager) => Ok(manager.get_password(account)),
+            _ => Err(NotUnlocked),
+        }
+    }
+
+    /// Lock the mutex around the manager, panicking if a previous change of state panicked and left the manager without a state.
+    fn inner(&self) -> MutexGuard<'_, ManagerInner> {
+        self.0
+            .lock()
+            .expect("The shared manager shouldn't have been left without a state by a panic")
+    }
+}
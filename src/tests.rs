@@ -1,6 +1,16 @@
 //! Testing the password manager.
 
-use crate::password_manager::PasswordManagerBuilder;
+use std::collections::{BTreeMap, HashMap};
+use std::time::{Duration, Instant};
+
+use crate::generator::{generate_password, CharsetOptions};
+use crate::password_manager::{
+    unlock_all, AccountEntry, AccountNotFound, AutoLock, BuildError, CaseCollisionError,
+    CommonPasswordError, CsvError, Dirty, DuplicateAccountError, Expired, FailedUnlock, Frozen,
+    InsertError, Locked, MergeStrategy, MinLengthPolicy, NeedsSetup, ParseError, PasswordManager,
+    PasswordManagerBuilder, PasswordPolicy, PasswordStrength, PolicyError, Saved, SecretString,
+    TooShortError, TransitionEvent, UnlockError, Unlocked, VaultDiff, WeakPasswordError,
+};
 
 /// Test that unlocking the password manager using the same master password it was created with actually unlocks it.
 #[test]
@@ -44,7 +54,7 @@ fn retrieving_present_password_unlocked_manager_works() {
 
     let retrieved_password = manager.get_password(ACCOUNT);
 
-    assert_eq!(retrieved_password, Some(String::from(PASSWORD)));
+    assert_eq!(retrieved_password, Some(SecretString::from(PASSWORD)));
 }
 
 /// Ensure retrieval of passwords known not to be stored fails.
@@ -62,3 +72,3557 @@ fn retrieving_nonexistant_password_fails() {
 
     assert_eq!(retrieved_password, None);
 }
+
+/// Test that `try_unlock` with the correct master password unlocks the manager.
+#[test]
+fn try_unlocking_manager_with_correct_password_works() {
+    const MASTER_PASSWORD: &str = "Master Password";
+
+    let manager = PasswordManagerBuilder::new()
+        .with_master_password(MASTER_PASSWORD)
+        .build();
+
+    assert!(manager.try_unlock(MASTER_PASSWORD).is_ok());
+}
+
+/// Test that `try_unlock` with an incorrect master password gives back the locked manager along with the reason.
+#[test]
+fn try_unlocking_manager_with_incorrect_password_reports_wrong_password() {
+    const MASTER_PASSWORD: &str = "Master Password";
+
+    let manager = PasswordManagerBuilder::new()
+        .with_master_password(MASTER_PASSWORD)
+        .build();
+
+    let (still_locked, error) = manager
+        .try_unlock(format!("Not {MASTER_PASSWORD}"))
+        .expect_err("Unlocking with an incorrect master password should fail");
+
+    assert_eq!(error, UnlockError::WrongPassword);
+    assert!(still_locked.unlock(MASTER_PASSWORD).is_ok());
+}
+
+/// Ensure the constant-time comparison used by `unlock` still tells apart passwords that share a prefix or length with the master password.
+#[test]
+fn unlocking_manager_with_similar_passwords_fails() {
+    const MASTER_PASSWORD: &str = "Master Password";
+
+    let attempts = [
+        "Master Passwore",
+        "Master Passwor",
+        "Master Password ",
+        "",
+        "\0Master Password",
+    ];
+
+    let mut manager = PasswordManagerBuilder::new()
+        .with_master_password(MASTER_PASSWORD)
+        .build();
+
+    for attempt in attempts {
+        manager = manager
+            .unlock(attempt)
+            .expect_err("Unlocking with a similar but incorrect password should fail");
+    }
+
+    assert!(manager.unlock(MASTER_PASSWORD).is_ok());
+}
+
+/// Ensure removing an account returns its password and that it can no longer be retrieved afterwards.
+#[test]
+fn removing_account_works() {
+    const MASTER_PASSWORD: &str = "Master Password";
+    const ACCOUNT: &str = "Account";
+    const PASSWORD: &str = "Hunter2";
+
+    let manager = PasswordManagerBuilder::new()
+        .with_master_password(MASTER_PASSWORD)
+        .with_account(ACCOUNT, PASSWORD)
+        .build()
+        .unlock(MASTER_PASSWORD)
+        .expect("Unlocking with correct master password should work");
+
+    let (mut manager, password) = manager.remove_account(ACCOUNT);
+    assert_eq!(password, Some(SecretString::from(PASSWORD)));
+    assert_eq!(manager.get_password(ACCOUNT), None);
+    assert_eq!(manager.remove_account(ACCOUNT), None);
+}
+
+/// A custom account information type used to test managers with non-default type parameters.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "encryption", derive(serde::Serialize, serde::Deserialize))]
+struct Secret {
+    password: String,
+    pin: u16,
+}
+
+#[cfg(feature = "zeroize")]
+impl zeroize::Zeroize for Secret {
+    fn zeroize(&mut self) {
+        self.password.zeroize();
+        self.pin.zeroize();
+    }
+}
+
+/// Ensure a password manager can store account types other than [String] mapping to password types other than [String].
+#[test]
+fn manager_with_custom_account_and_password_types_works() {
+    const MASTER_PASSWORD: &str = "Master Password";
+
+    let first = Secret {
+        password: String::from("Hunter2"),
+        pin: 1234,
+    };
+    let second = Secret {
+        password: String::from("Bees123"),
+        pin: 4321,
+    };
+
+    let manager: PasswordManager<Locked, u32, Secret> = PasswordManagerBuilder::default()
+        .with_master_password(MASTER_PASSWORD)
+        .with_account(1u32, first.clone())
+        .build();
+    let mut manager = manager
+        .unlock(MASTER_PASSWORD)
+        .expect("Unlocking with correct master password should work")
+        .insert(2u32, second.clone());
+
+    assert_eq!(manager.get_password(&1), Some(first));
+    assert_eq!(manager.remove_account(&2), Some(second));
+    assert_eq!(manager.get_password(&2), None);
+    assert_eq!(manager.get_password(&3), None);
+}
+
+/// Ensure a locked manager survives a round trip through JSON and still unlocks with the original master password.
+#[cfg(feature = "serde")]
+#[test]
+fn serializing_locked_manager_round_trips() {
+    const MASTER_PASSWORD: &str = "Master Password";
+    const ACCOUNT: &str = "Account";
+    const PASSWORD: &str = "Hunter2";
+
+    let manager = PasswordManagerBuilder::new()
+        .with_master_password(MASTER_PASSWORD)
+        .with_account(ACCOUNT, PASSWORD)
+        .build();
+
+    let json = serde_json::to_string(&manager).expect("Serializing a locked manager should work");
+    let manager: PasswordManager =
+        serde_json::from_str(&json).expect("Deserializing a serialized manager should work");
+
+    let manager = manager
+        .unlock(MASTER_PASSWORD)
+        .expect("Unlocking with correct master password should work");
+
+    assert_eq!(
+        manager.get_password(ACCOUNT),
+        Some(SecretString::from(PASSWORD))
+    );
+}
+
+/// Ensure a locked manager survives a round trip through a file and still unlocks with the original master password.
+#[test]
+fn saving_locked_manager_to_file_round_trips() {
+    const MASTER_PASSWORD: &str = "Master Password";
+
+    let mut manager = PasswordManager::<Unlocked>::from_accounts(
+        MASTER_PASSWORD,
+        [
+            ("test@example.com", "Bees123"),
+            ("me@news.biz", "Hornets789"),
+        ],
+    )
+    .insert("test@example.com", "Wasps456");
+    manager.set_url("test@example.com", Some("https://example.com".to_string()));
+    manager.add_tag("me@news.biz", "news");
+    let file = tempfile::NamedTempFile::new().expect("Creating a temporary file should work");
+
+    manager
+        .mark_saved()
+        .lock()
+        .save_to_file(file.path())
+        .expect("Saving a locked manager should work");
+    let manager = PasswordManager::load_from_file(file.path())
+        .expect("Loading a saved manager should work")
+        .unlock(MASTER_PASSWORD)
+        .expect("Unlocking with correct master password should work");
+
+    assert_eq!(manager.len(), 2);
+    let entry = manager
+        .get_entry("test@example.com")
+        .expect("Account should have been loaded");
+    assert_eq!(entry.password, *"Wasps456");
+    assert_eq!(entry.url.as_deref(), Some("https://example.com"));
+    assert_eq!(entry.history, [SecretString::from("Bees123")]);
+    assert_eq!(
+        manager
+            .get_entry("me@news.biz")
+            .map(|entry| &entry.tags[..]),
+        Some(&["news".to_string()][..])
+    );
+}
+
+/// Ensure inserting into a saved manager marks it as dirty, and saving it to a file marks it as saved again so it can be locked.
+#[test]
+fn saving_dirty_manager_to_file_marks_it_saved() {
+    const MASTER_PASSWORD: &str = "Master Password";
+
+    let manager: PasswordManager<Unlocked<Saved>> = PasswordManager::<Unlocked>::from_accounts(
+        MASTER_PASSWORD,
+        [("test@example.com", "Bees123")],
+    );
+    let mut manager: PasswordManager<Unlocked<Dirty>> = manager.insert("me@news.biz", "Hornets789");
+    manager.insert("person@social.com", "Wasps456");
+    assert_eq!(
+        manager.remove_account("test@example.com"),
+        Some("Bees123".into())
+    );
+
+    // A failed save hands the manager back with its changes still unsaved.
+    let directory = tempfile::tempdir().expect("Creating a temporary directory should work");
+    let (manager, _) = manager
+        .save_to_file(directory.path())
+        .expect_err("Saving over a directory should fail");
+    assert_eq!(manager.len(), 2);
+
+    let file = tempfile::NamedTempFile::new().expect("Creating a temporary file should work");
+    let manager: PasswordManager<Unlocked<Saved>> = manager
+        .save_to_file(file.path())
+        .expect("Saving a dirty manager should work");
+    assert_eq!(manager.len(), 2);
+    manager.lock();
+
+    let manager = PasswordManager::load_from_file(file.path())
+        .expect("Loading a saved manager should work")
+        .unlock(MASTER_PASSWORD)
+        .expect("Unlocking with correct master password should work");
+    assert_eq!(manager.len(), 2);
+    assert_eq!(manager.get_password_ref("me@news.biz"), Some("Hornets789"));
+    assert!(!manager.contains_account("test@example.com"));
+}
+
+/// Ensure a dirty manager saved to a file writes the file header, and only leaves its passwords readable in the file without the encryption
+/// feature.
+#[test]
+fn saving_dirty_manager_writes_file_format() {
+    const MASTER_PASSWORD: &str = "Master Password";
+    const PASSWORD: &str = "Hornets789";
+
+    let manager = PasswordManager::<Unlocked>::from_accounts(
+        MASTER_PASSWORD,
+        [("test@example.com", "Bees123")],
+    )
+    .insert("me@news.biz", PASSWORD);
+    let file = tempfile::NamedTempFile::new().expect("Creating a temporary file should work");
+    manager
+        .save_to_file(file.path())
+        .expect("Saving a dirty manager should work");
+
+    let bytes = std::fs::read(file.path()).expect("Reading the saved file should work");
+    assert_eq!(&bytes[..5], b"RTPM\x01");
+    let contains_password = bytes
+        .windows(PASSWORD.len())
+        .any(|window| window == PASSWORD.as_bytes());
+    assert_eq!(contains_password, !cfg!(feature = "encryption"));
+}
+
+/// Ensure removing an account from a saved manager marks it as dirty until it is marked as saved.
+#[test]
+fn removing_account_marks_manager_dirty() {
+    const MASTER_PASSWORD: &str = "Master Password";
+
+    let manager = PasswordManager::<Unlocked>::from_accounts(
+        MASTER_PASSWORD,
+        [("test@example.com", "Bees123")],
+    );
+    let (manager, password): (PasswordManager<Unlocked<Dirty>>, _) =
+        manager.remove_account("test@example.com");
+    assert_eq!(password, Some("Bees123".into()));
+
+    let manager: PasswordManager<Unlocked<Saved>> = manager.mark_saved();
+    let manager = manager
+        .lock()
+        .unlock(MASTER_PASSWORD)
+        .expect("Unlocking with correct master password should work");
+    assert!(manager.is_empty());
+}
+
+/// Ensure loading a file that wasn't written by a password manager fails.
+#[test]
+fn loading_invalid_file_fails() {
+    let mut file = tempfile::NamedTempFile::new().expect("Creating a temporary file should work");
+    std::io::Write::write_all(&mut file, b"Not a password manager")
+        .expect("Writing a temporary file should work");
+
+    let error = PasswordManager::load_from_file(file.path())
+        .expect_err("Loading an invalid file should fail");
+    assert_eq!(error.kind(), std::io::ErrorKind::InvalidData);
+}
+
+/// Ensure a locked manager only holds its accounts encrypted, so none of its passwords can be found in the file it is saved to.
+#[cfg(feature = "encryption")]
+#[test]
+fn locked_manager_encrypts_passwords() {
+    const MASTER_PASSWORD: &str = "Master Password";
+    const ACCOUNTS: [(&str, &str); 2] = [
+        ("test@example.com", "Bees123"),
+        ("me@news.biz", "Hornets789"),
+    ];
+
+    let manager = PasswordManagerBuilder::new()
+        .with_master_password(MASTER_PASSWORD)
+        .with_accounts(ACCOUNTS)
+        .build();
+    let file = tempfile::NamedTempFile::new().expect("Creating a temporary file should work");
+    manager
+        .save_to_file(file.path())
+        .expect("Saving a locked manager should work");
+
+    let bytes = std::fs::read(file.path()).expect("Reading the saved file should work");
+    for (_, password) in ACCOUNTS {
+        assert!(!bytes
+            .windows(password.len())
+            .any(|window| window == password.as_bytes()));
+    }
+    assert_eq!(manager.to_string(), "PasswordManager(locked, 2 accounts)");
+
+    let manager = manager
+        .unlock(MASTER_PASSWORD)
+        .expect("Unlocking with correct master password should work");
+    for (account, password) in ACCOUNTS {
+        assert_eq!(manager.get_password(account), Some(password.into()));
+    }
+}
+
+/// Ensure a manager whose encrypted accounts have been tampered with refuses to unlock, even with the correct master password.
+#[cfg(feature = "encryption")]
+#[test]
+fn tampered_manager_fails_to_decrypt() {
+    const MASTER_PASSWORD: &str = "Master Password";
+
+    let manager = PasswordManagerBuilder::new()
+        .with_master_password(MASTER_PASSWORD)
+        .with_account("test@example.com", "Bees123")
+        .build();
+    let file = tempfile::NamedTempFile::new().expect("Creating a temporary file should work");
+    manager
+        .save_to_file(file.path())
+        .expect("Saving a locked manager should work");
+
+    // The encrypted accounts are the last thing in the file.
+    let mut bytes = std::fs::read(file.path()).expect("Reading the saved file should work");
+    *bytes.last_mut().expect("The file shouldn't be empty") ^= 1;
+    std::fs::write(file.path(), bytes).expect("Writing the saved file should work");
+
+    let manager =
+        PasswordManager::load_from_file(file.path()).expect("Loading a saved manager should work");
+    assert!(manager.unlock(MASTER_PASSWORD).is_err());
+}
+
+/// Ensure a deserialized manager is locked and won't unlock with the wrong master password.
+///
+/// The master password is only serialized as plaintext without the hashing feature.
+#[cfg(all(feature = "serde", not(feature = "hashing")))]
+#[test]
+fn deserialized_manager_is_locked() {
+    let json = r#"{"master_password":"Master Password","password_list":{"Account":"Hunter2"}}"#;
+
+    let manager: PasswordManager =
+        serde_json::from_str(json).expect("Deserializing a valid manager should work");
+
+    assert!(manager.unlock("Not Master Password").is_err());
+}
+
+/// A stored password that records whether it was zeroized by the time it was dropped.
+#[cfg(feature = "zeroize")]
+#[derive(Debug)]
+struct DropRecorder {
+    zeroized: bool,
+    drops: std::rc::Rc<std::cell::RefCell<Vec<bool>>>,
+}
+
+#[cfg(feature = "zeroize")]
+impl zeroize::Zeroize for DropRecorder {
+    fn zeroize(&mut self) {
+        self.zeroized = true;
+    }
+}
+
+#[cfg(feature = "zeroize")]
+impl Drop for DropRecorder {
+    fn drop(&mut self) {
+        self.drops.borrow_mut().push(self.zeroized);
+    }
+}
+
+/// Ensure stored passwords are zeroized when the manager is dropped but not when it moves between states.
+///
+/// With the encryption feature the passwords are zeroized as soon as they are encrypted, so this only applies without it.
+#[cfg(all(feature = "zeroize", not(feature = "encryption")))]
+#[test]
+fn dropping_manager_zeroizes_passwords() {
+    const MASTER_PASSWORD: &str = "Master Password";
+
+    let drops = std::rc::Rc::new(std::cell::RefCell::new(Vec::new()));
+    let recorder = || DropRecorder {
+        zeroized: false,
+        drops: drops.clone(),
+    };
+
+    let manager: PasswordManager<Locked, String, DropRecorder> = PasswordManagerBuilder::default()
+        .with_master_password(MASTER_PASSWORD)
+        .build();
+    let mut manager = manager
+        .unlock(MASTER_PASSWORD)
+        .expect("Unlocking with correct master password should work")
+        .insert("First", recorder());
+    manager.insert("Second", recorder());
+
+    let manager = manager
+        .mark_saved()
+        .lock()
+        .unlock(MASTER_PASSWORD)
+        .expect("Unlocking with correct master password should work");
+    assert!(drops.borrow().is_empty());
+
+    drop(manager);
+    assert_eq!(*drops.borrow(), vec![true, true]);
+}
+
+#[cfg(feature = "encryption")]
+std::thread_local! {
+    /// Whether each dropped [SealRecorder] was zeroized first, in the order they were dropped.
+    static SEALED_DROPS: std::cell::RefCell<Vec<bool>> = const { std::cell::RefCell::new(Vec::new()) };
+}
+
+/// A stored password that records whether it was zeroized by the time it was dropped, which can be encrypted unlike [DropRecorder].
+#[cfg(feature = "encryption")]
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+struct SealRecorder {
+    #[serde(skip)]
+    zeroized: bool,
+}
+
+#[cfg(feature = "encryption")]
+impl zeroize::Zeroize for SealRecorder {
+    fn zeroize(&mut self) {
+        self.zeroized = true;
+    }
+}
+
+#[cfg(feature = "encryption")]
+impl Drop for SealRecorder {
+    fn drop(&mut self) {
+        SEALED_DROPS.with_borrow_mut(|drops| drops.push(self.zeroized));
+    }
+}
+
+/// Ensure the plaintext passwords are zeroized as soon as locking has encrypted them.
+#[cfg(feature = "encryption")]
+#[test]
+fn locking_zeroizes_encrypted_passwords() {
+    const MASTER_PASSWORD: &str = "Master Password";
+
+    let manager: PasswordManager<Locked, String, SealRecorder> = PasswordManagerBuilder::default()
+        .with_master_password(MASTER_PASSWORD)
+        .build();
+    let mut manager = manager
+        .unlock(MASTER_PASSWORD)
+        .expect("Unlocking with correct master password should work")
+        .insert("First", SealRecorder { zeroized: false });
+    manager.insert("Second", SealRecorder { zeroized: false });
+    SEALED_DROPS.with_borrow_mut(Vec::clear);
+
+    let manager = manager.mark_saved().lock();
+    assert_eq!(manager.account_count(), 2);
+    SEALED_DROPS.with_borrow(|drops| assert_eq!(*drops, vec![true, true]));
+}
+
+/// Ensure iterating over an unlocked manager visits every stored account and password exactly once.
+#[test]
+fn iterating_manager_visits_every_pair_once() {
+    const MASTER_PASSWORD: &str = "Master Password";
+    const ACCOUNTS: [(&str, &str); 3] = [
+        ("test@example.com", "Bees123"),
+        ("person@social.com", "Wasps456"),
+        ("me@news.biz", "Hornets789"),
+    ];
+
+    let mut builder = PasswordManagerBuilder::new().with_master_password(MASTER_PASSWORD);
+    for (account, password) in ACCOUNTS {
+        builder = builder.with_account(account, password);
+    }
+    let manager = builder
+        .build()
+        .unlock(MASTER_PASSWORD)
+        .expect("Unlocking with correct master password should work");
+
+    let mut visited: Vec<(&str, &str)> = manager.iter().collect();
+    visited.sort();
+    let mut expected = ACCOUNTS.to_vec();
+    expected.sort();
+
+    assert_eq!(visited, expected);
+}
+
+/// Ensure iterating over the account names of an unlocked manager yields every account exactly once.
+#[test]
+fn iterating_accounts_yields_every_account_once() {
+    const MASTER_PASSWORD: &str = "Master Password";
+
+    let manager = PasswordManagerBuilder::new()
+        .with_master_password(MASTER_PASSWORD)
+        .with_account("test@example.com", "Bees123")
+        .with_account("person@social.com", "Wasps456")
+        .build()
+        .unlock(MASTER_PASSWORD)
+        .expect("Unlocking with correct master password should work");
+
+    let mut accounts: Vec<&str> = manager.accounts().collect();
+    accounts.sort();
+
+    assert_eq!(accounts, ["person@social.com", "test@example.com"]);
+}
+
+/// Ensure changing the master password means only the new password unlocks the manager afterwards.
+#[test]
+fn changing_master_password_works() {
+    const OLD_MASTER_PASSWORD: &str = "Master Password";
+    const NEW_MASTER_PASSWORD: &str = "New Master Password";
+
+    let mut manager = PasswordManagerBuilder::new()
+        .with_master_password(OLD_MASTER_PASSWORD)
+        .build()
+        .unlock(OLD_MASTER_PASSWORD)
+        .expect("Unlocking with correct master password should work")
+        .edit();
+
+    manager.change_master_password(NEW_MASTER_PASSWORD);
+
+    let manager = manager
+        .mark_saved()
+        .lock()
+        .unlock(OLD_MASTER_PASSWORD)
+        .expect_err("Unlocking with the old master password should fail");
+
+    assert!(manager.unlock(NEW_MASTER_PASSWORD).is_ok());
+}
+
+/// Ensure a manager with limited attempts transitions to `LockedOut` after exactly the configured number of wrong guesses.
+#[test]
+fn manager_locks_out_after_max_attempts() {
+    const MASTER_PASSWORD: &str = "Master Password";
+    const MAX_ATTEMPTS: u8 = 3;
+
+    let mut manager = PasswordManagerBuilder::new()
+        .with_master_password(MASTER_PASSWORD)
+        .with_max_attempts(MAX_ATTEMPTS)
+        .build();
+    assert_eq!(manager.attempts_remaining(), Some(MAX_ATTEMPTS));
+
+    for remaining in (1..MAX_ATTEMPTS).rev() {
+        manager = match manager.unlock_or_lock_out("Not Master Password") {
+            Err(FailedUnlock::Locked(still_locked)) => still_locked,
+            other => panic!("Expected the manager to still be locked, got {other:?}"),
+        };
+        assert_eq!(manager.attempts_remaining(), Some(remaining));
+    }
+
+    assert!(matches!(
+        manager.unlock_or_lock_out("Not Master Password"),
+        Err(FailedUnlock::LockedOut(_))
+    ));
+}
+
+/// Ensure a manager that has run out of attempts won't unlock even with the correct master password.
+#[test]
+fn manager_out_of_attempts_refuses_correct_password() {
+    const MASTER_PASSWORD: &str = "Master Password";
+
+    let manager = PasswordManagerBuilder::new()
+        .with_master_password(MASTER_PASSWORD)
+        .with_max_attempts(1)
+        .build();
+
+    let (manager, error) = manager
+        .try_unlock("Not Master Password")
+        .expect_err("Unlocking with an incorrect master password should fail");
+    assert_eq!(error, UnlockError::LockedOut);
+
+    let (_, error) = manager
+        .try_unlock(MASTER_PASSWORD)
+        .expect_err("Unlocking a manager with no attempts remaining should fail");
+    assert_eq!(error, UnlockError::LockedOut);
+}
+
+/// Ensure a successful unlock restores the manager's attempts for the next time it is locked.
+#[test]
+fn unlocking_manager_resets_attempts() {
+    const MASTER_PASSWORD: &str = "Master Password";
+
+    let manager = PasswordManagerBuilder::new()
+        .with_master_password(MASTER_PASSWORD)
+        .with_max_attempts(2)
+        .build()
+        .unlock("Not Master Password")
+        .expect_err("Unlocking with an incorrect master password should fail");
+    assert_eq!(manager.attempts_remaining(), Some(1));
+
+    let manager = manager
+        .unlock(MASTER_PASSWORD)
+        .expect("Unlocking with correct master password should work")
+        .lock();
+
+    assert_eq!(manager.attempts_remaining(), Some(2));
+}
+
+/// Ensure every wrong master password is counted, even without a limit on attempts.
+#[test]
+fn failed_attempts_are_counted() {
+    const MASTER_PASSWORD: &str = "Master Password";
+
+    let mut manager = PasswordManagerBuilder::new()
+        .with_master_password(MASTER_PASSWORD)
+        .build();
+    assert_eq!(manager.failed_attempts(), 0);
+
+    for failed_attempts in 1..=3 {
+        manager = manager
+            .unlock("Not Master Password")
+            .expect_err("Unlocking with an incorrect master password should fail");
+        assert_eq!(manager.failed_attempts(), failed_attempts);
+    }
+    assert_eq!(manager.attempts_remaining(), None);
+}
+
+/// Ensure a successful unlock starts the count of failed attempts again from zero.
+#[test]
+fn unlocking_manager_resets_failed_attempts() {
+    const MASTER_PASSWORD: &str = "Master Password";
+
+    let mut manager = PasswordManagerBuilder::new()
+        .with_master_password(MASTER_PASSWORD)
+        .build();
+    assert!(manager.unlock_ref("Not Master Password").is_none());
+    assert_eq!(manager.failed_attempts(), 1);
+
+    let manager = manager
+        .unlock("Not Master Password")
+        .expect_err("Unlocking with an incorrect master password should fail");
+    assert_eq!(manager.failed_attempts(), 2);
+
+    let manager = manager
+        .unlock(MASTER_PASSWORD)
+        .expect("Unlocking with correct master password should work")
+        .lock();
+    assert_eq!(manager.failed_attempts(), 0);
+}
+
+/// Ensure unlocking a copy of a manager with `unlock_ref` also resets the failed attempts of the manager left locked.
+#[test]
+fn unlocking_ref_resets_failed_attempts() {
+    const MASTER_PASSWORD: &str = "Master Password";
+
+    let mut manager = PasswordManagerBuilder::new()
+        .with_master_password(MASTER_PASSWORD)
+        .build();
+    assert!(manager.unlock_ref("Not Master Password").is_none());
+    assert!(manager.unlock_ref("Not Master Password").is_none());
+    assert_eq!(manager.failed_attempts(), 2);
+
+    assert!(manager.unlock_ref(MASTER_PASSWORD).is_some());
+    assert_eq!(manager.failed_attempts(), 0);
+}
+
+/// Ensure a cloned locked manager unlocks with the same master password as the original, and changes to one don't affect the other.
+#[test]
+fn cloned_locked_manager_unlocks_independently() {
+    const MASTER_PASSWORD: &str = "Master Password";
+
+    let manager = PasswordManagerBuilder::new()
+        .with_master_password(MASTER_PASSWORD)
+        .with_account("test@example.com", "Bees123")
+        .build();
+    let copy = manager.clone();
+
+    let manager = manager
+        .unlock(MASTER_PASSWORD)
+        .expect("Unlocking the original with correct master password should work")
+        .insert("test@example.com", "Wasps456");
+    let copy = copy
+        .unlock(MASTER_PASSWORD)
+        .expect("Unlocking the clone with correct master password should work");
+
+    assert_eq!(
+        manager.get_password("test@example.com"),
+        Some("Wasps456".into())
+    );
+    assert_eq!(
+        copy.get_password("test@example.com"),
+        Some("Bees123".into())
+    );
+}
+
+/// Ensure the master password can be read from an environment variable.
+///
+/// Tests run in parallel and share the environment, so each test that sets a variable uses one with its own name.
+#[test]
+fn building_with_master_password_from_env_works() {
+    const VAR: &str = "RUST_TYPESTATE_TEST_MASTER_PASSWORD_FROM_ENV";
+    const MASTER_PASSWORD: &str = "Master Password";
+
+    std::env::set_var(VAR, MASTER_PASSWORD);
+    let builder = PasswordManagerBuilder::new().with_master_password_from_env(VAR);
+    std::env::remove_var(VAR);
+
+    let manager = builder
+        .expect("Reading a set environment variable should work")
+        .build();
+    assert!(manager.unlock(MASTER_PASSWORD).is_ok());
+}
+
+/// Ensure reading the master password from an environment variable that isn't set fails.
+#[test]
+fn building_with_master_password_from_missing_env_fails() {
+    let result = PasswordManagerBuilder::new()
+        .with_master_password_from_env("RUST_TYPESTATE_TEST_MISSING_MASTER_PASSWORD");
+
+    assert!(matches!(result, Err(std::env::VarError::NotPresent)));
+}
+
+/// Ensure a locked manager can report how many accounts it holds without being unlocked.
+#[test]
+fn locked_manager_counts_accounts() {
+    let manager = PasswordManagerBuilder::new()
+        .with_master_password("Master Password")
+        .with_accounts([
+            ("test@example.com", "Bees123"),
+            ("person@social.com", "Wasps456"),
+            ("me@news.biz", "Hornets789"),
+        ])
+        .build();
+
+    assert_eq!(manager.account_count(), 3);
+}
+
+/// Ensure a manager guarded by [AutoLock] ends up locked when a panic unwinds past the guard, keeping the changes made before the panic.
+#[test]
+fn auto_lock_locks_manager_on_panic() {
+    const MASTER_PASSWORD: &str = "Master Password";
+
+    let mut locked = None;
+    let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+        let mut manager = AutoLock::new(
+            PasswordManager::<Unlocked>::from_accounts(
+                MASTER_PASSWORD,
+                [("test@example.com", "Bees123")],
+            ),
+            &mut locked,
+        );
+        manager.insert("me@news.biz", "Hornets789");
+        panic!("Something went wrong while the manager was unlocked");
+    }));
+    assert!(result.is_err());
+
+    let manager = locked
+        .expect("The guard should have locked the manager")
+        .unlock(MASTER_PASSWORD)
+        .expect("Unlocking with correct master password should work");
+    assert_eq!(manager.len(), 2);
+}
+
+/// Ensure a manager taken back out of an [AutoLock] stays unlocked.
+#[test]
+fn auto_lock_into_inner_keeps_manager_unlocked() {
+    let mut locked = None;
+    let manager = AutoLock::new(
+        PasswordManager::<Unlocked>::from_accounts(
+            "Master Password",
+            [("test@example.com", "Bees123")],
+        ),
+        &mut locked,
+    )
+    .into_inner();
+
+    assert!(locked.is_none());
+    assert_eq!(
+        manager.get_password("test@example.com"),
+        Some("Bees123".into())
+    );
+}
+
+/// Ensure `try_build` builds a working manager when the master password has been set.
+#[test]
+fn try_building_with_master_password_works() {
+    const MASTER_PASSWORD: &str = "Master Password";
+
+    let manager = PasswordManagerBuilder::new()
+        .with_master_password(MASTER_PASSWORD)
+        .try_build()
+        .expect("Building with a master password should work");
+
+    assert!(manager.unlock(MASTER_PASSWORD).is_ok());
+}
+
+/// Ensure `try_build` reports a missing master password instead of building.
+#[test]
+fn try_building_without_master_password_fails() {
+    let result = PasswordManagerBuilder::new()
+        .with_account("Account", "Hunter2")
+        .try_build();
+
+    assert_eq!(result.unwrap_err(), BuildError::MissingMasterPassword);
+}
+
+/// Ensure bulk-adding accounts to a builder stores every account, with later duplicates overwriting earlier ones.
+#[test]
+fn building_with_accounts_works() {
+    const MASTER_PASSWORD: &str = "Master Password";
+
+    let accounts = vec![
+        ("test@example.com", "Bees123"),
+        ("person@social.com", "Wasps456"),
+        ("test@example.com", "Hornets789"),
+    ];
+
+    let manager = PasswordManagerBuilder::new()
+        .with_master_password(MASTER_PASSWORD)
+        .with_accounts(accounts)
+        .build()
+        .unlock(MASTER_PASSWORD)
+        .expect("Unlocking with correct master password should work");
+
+    assert_eq!(manager.get_passwords().len(), 2);
+    assert_eq!(
+        manager.get_password("test@example.com"),
+        Some(SecretString::from("Hornets789"))
+    );
+    assert_eq!(
+        manager.get_password("person@social.com"),
+        Some(SecretString::from("Wasps456"))
+    );
+}
+
+/// A stored password that counts how many times it has been cloned.
+///
+/// It shares its count with every clone, which can't survive being serialized, so it can't be used with the encryption feature.
+#[cfg(not(feature = "encryption"))]
+#[derive(Debug)]
+struct CloneCounter(std::rc::Rc<std::cell::Cell<usize>>);
+
+#[cfg(not(feature = "encryption"))]
+impl Clone for CloneCounter {
+    fn clone(&self) -> Self {
+        self.0.set(self.0.get() + 1);
+        Self(self.0.clone())
+    }
+}
+
+#[cfg(all(feature = "zeroize", not(feature = "encryption")))]
+impl zeroize::Zeroize for CloneCounter {
+    fn zeroize(&mut self) {}
+}
+
+/// Ensure chaining many `with_account` calls never clones the accounts already added to the builder.
+#[cfg(not(feature = "encryption"))]
+#[test]
+fn building_with_many_accounts_does_not_clone() {
+    const MASTER_PASSWORD: &str = "Master Password";
+
+    let clones = std::rc::Rc::new(std::cell::Cell::new(0));
+
+    let mut builder = PasswordManagerBuilder::default().with_master_password(MASTER_PASSWORD);
+    for account in 0..100u32 {
+        builder = builder.with_account(account, CloneCounter(clones.clone()));
+    }
+    let manager: PasswordManager<Locked, u32, CloneCounter> = builder.build();
+    let manager = manager
+        .unlock(MASTER_PASSWORD)
+        .expect("Unlocking with correct master password should work");
+
+    assert_eq!(clones.get(), 0);
+    assert!(manager.get_password(&99).is_some());
+    assert_eq!(clones.get(), 1);
+}
+
+/// Ensure an unlocked manager with no accounts reports itself as empty.
+#[test]
+fn empty_manager_has_no_accounts() {
+    const MASTER_PASSWORD: &str = "Master Password";
+
+    let manager = PasswordManagerBuilder::new()
+        .with_master_password(MASTER_PASSWORD)
+        .build()
+        .unlock(MASTER_PASSWORD)
+        .expect("Unlocking with correct master password should work");
+
+    assert_eq!(manager.len(), 0);
+    assert!(manager.is_empty());
+    assert!(!manager.contains_account("Account"));
+}
+
+/// Ensure an unlocked manager reports the number of stored accounts and which accounts it contains.
+#[test]
+fn populated_manager_contains_accounts() {
+    const MASTER_PASSWORD: &str = "Master Password";
+
+    let manager = PasswordManagerBuilder::new()
+        .with_master_password(MASTER_PASSWORD)
+        .with_account("test@example.com", "Bees123")
+        .with_account("person@social.com", "Wasps456")
+        .build()
+        .unlock(MASTER_PASSWORD)
+        .expect("Unlocking with correct master password should work");
+
+    assert_eq!(manager.len(), 2);
+    assert!(!manager.is_empty());
+    assert!(manager.contains_account("test@example.com"));
+    assert!(!manager.contains_account("me@news.biz"));
+}
+
+/// Ensure displaying a manager shows its state and account count without leaking any secrets or account names.
+#[test]
+fn displaying_manager_does_not_leak_secrets() {
+    const MASTER_PASSWORD: &str = "Master Password";
+    const ACCOUNTS: [(&str, &str); 3] = [
+        ("test@example.com", "Bees123"),
+        ("person@social.com", "Wasps456"),
+        ("me@news.biz", "Hornets789"),
+    ];
+
+    let manager = PasswordManagerBuilder::new()
+        .with_master_password(MASTER_PASSWORD)
+        .with_accounts(ACCOUNTS)
+        .build();
+    let locked = manager.to_string();
+
+    let manager = manager
+        .unlock(MASTER_PASSWORD)
+        .expect("Unlocking with correct master password should work");
+    let unlocked = manager.to_string();
+
+    assert_eq!(locked, "PasswordManager(locked, 3 accounts)");
+    assert_eq!(unlocked, "PasswordManager(unlocked, 3 accounts)");
+    for rendered in [locked, unlocked] {
+        assert!(!rendered.contains(MASTER_PASSWORD));
+        for (account, password) in ACCOUNTS {
+            assert!(!rendered.contains(account));
+            assert!(!rendered.contains(password));
+        }
+    }
+}
+
+/// Ensure debug formatting a manager redacts the master password and stored passwords but still shows its state.
+#[test]
+fn debug_formatting_manager_redacts_secrets() {
+    const MASTER_PASSWORD: &str = "Master Password";
+    const ACCOUNT: &str = "test@example.com";
+    const PASSWORD: &str = "Bees123";
+
+    let manager = PasswordManagerBuilder::new()
+        .with_master_password(MASTER_PASSWORD)
+        .with_account(ACCOUNT, PASSWORD)
+        .build();
+    let locked = format!("{manager:?}");
+
+    let manager = manager
+        .unlock(MASTER_PASSWORD)
+        .expect("Unlocking with correct master password should work");
+    let unlocked = format!("{manager:?}");
+
+    assert!(locked.contains("Locked"));
+    assert!(unlocked.contains("Unlocked"));
+    for rendered in [locked, unlocked] {
+        assert!(rendered.contains("master_password: \"<redacted>\""));
+        assert!(rendered.contains("password_list: {<1 entries>}"));
+        assert!(!rendered.contains(MASTER_PASSWORD));
+        assert!(!rendered.contains(PASSWORD));
+    }
+}
+
+/// Ensure a password can be edited in place through a mutable reference.
+#[test]
+fn editing_password_in_place_works() {
+    const MASTER_PASSWORD: &str = "Master Password";
+    const ACCOUNT: &str = "test@example.com";
+
+    let mut manager = PasswordManagerBuilder::new()
+        .with_master_password(MASTER_PASSWORD)
+        .with_account(ACCOUNT, "Bees")
+        .build()
+        .unlock(MASTER_PASSWORD)
+        .expect("Unlocking with correct master password should work")
+        .edit();
+
+    manager
+        .get_password_mut(ACCOUNT)
+        .expect("Account should be present")
+        .expose_mut()
+        .push_str("123");
+
+    assert_eq!(manager.get_password(ACCOUNT), Some("Bees123".into()));
+    assert!(manager.get_password_mut("me@news.biz").is_none());
+}
+
+/// Ensure a manager created straight from accounts is unlocked, holds the accounts, and needs its master password once locked.
+#[test]
+fn creating_manager_from_accounts_works() {
+    const MASTER_PASSWORD: &str = "Master Password";
+    const ACCOUNTS: [(&str, &str); 2] = [
+        ("test@example.com", "Bees123"),
+        ("person@social.com", "Wasps456"),
+    ];
+
+    let manager = PasswordManager::<Unlocked>::from_accounts(MASTER_PASSWORD, ACCOUNTS);
+
+    for (account, password) in ACCOUNTS {
+        assert_eq!(manager.get_password(account), Some(password.into()));
+    }
+
+    let manager = manager
+        .lock()
+        .unlock(format!("Not {MASTER_PASSWORD}"))
+        .expect_err("Unlocking with incorrect master password should fail");
+    assert!(manager.unlock(MASTER_PASSWORD).is_ok());
+}
+
+/// Ensure a master password that meets the minimum strength builds a manager.
+#[test]
+fn building_with_strong_enough_master_password_works() {
+    const MASTER_PASSWORD: &str = "Master Password 123";
+
+    let manager = PasswordManagerBuilder::new()
+        .with_master_password(MASTER_PASSWORD)
+        .try_build_with_min_strength(PasswordStrength::Medium)
+        .expect("Building with a strong master password should work");
+
+    assert!(manager.unlock(MASTER_PASSWORD).is_ok());
+}
+
+/// Ensure a master password weaker than the minimum strength is rejected along with its evaluated strength.
+#[test]
+fn building_with_weak_master_password_fails() {
+    let error = PasswordManagerBuilder::new()
+        .with_master_password("hunter2")
+        .try_build_with_min_strength(PasswordStrength::Medium)
+        .expect_err("Building with a weak master password should fail");
+
+    assert_eq!(
+        error,
+        WeakPasswordError {
+            strength: PasswordStrength::Weak
+        }
+    );
+}
+
+/// Ensure generated passwords are the requested length and contain every requested kind of character.
+#[test]
+fn generated_password_has_length_and_requested_characters() {
+    let opts = CharsetOptions {
+        symbols: false,
+        seed: Some(42),
+        ..CharsetOptions::default()
+    };
+
+    let password = generate_password(16, opts);
+
+    assert_eq!(password.len(), 16);
+    assert!(password.chars().any(|c| c.is_ascii_lowercase()));
+    assert!(password.chars().any(|c| c.is_ascii_uppercase()));
+    assert!(password.chars().any(|c| c.is_ascii_digit()));
+    assert!(password.chars().all(|c| c.is_ascii_alphanumeric()));
+    assert_eq!(password, generate_password(16, opts));
+}
+
+/// Ensure building with a generated account stores the generated password under that account.
+#[test]
+fn building_with_generated_account_works() {
+    const MASTER_PASSWORD: &str = "Master Password";
+    const ACCOUNT: &str = "test@example.com";
+
+    let opts = CharsetOptions {
+        seed: Some(7),
+        ..CharsetOptions::default()
+    };
+
+    let manager = PasswordManagerBuilder::new()
+        .with_master_password(MASTER_PASSWORD)
+        .with_generated_account(ACCOUNT, 20, opts)
+        .build()
+        .unlock(MASTER_PASSWORD)
+        .expect("Unlocking with correct master password should work");
+
+    assert_eq!(
+        manager.get_password(ACCOUNT),
+        Some(generate_password(20, opts).into())
+    );
+}
+
+/// Ensure a manager unlocked with a timeout gives out passwords until the timeout passes.
+#[test]
+fn manager_unlocked_with_timeout_works_before_expiry() {
+    const MASTER_PASSWORD: &str = "Master Password";
+    const ACCOUNT: &str = "test@example.com";
+    const PASSWORD: &str = "Bees123";
+
+    let manager = PasswordManagerBuilder::new()
+        .with_master_password(MASTER_PASSWORD)
+        .with_account(ACCOUNT, PASSWORD)
+        .build()
+        .unlock_with_timeout(MASTER_PASSWORD, Duration::from_secs(3600))
+        .expect("Unlocking with correct master password should work");
+
+    assert!(!manager.is_expired());
+    assert_eq!(
+        manager.get_password_checked(ACCOUNT),
+        Ok(Some(PASSWORD.into()))
+    );
+}
+
+/// Ensure a manager refuses to give out passwords once its timeout has passed.
+#[test]
+fn manager_unlocked_with_timeout_expires() {
+    const MASTER_PASSWORD: &str = "Master Password";
+    const ACCOUNT: &str = "test@example.com";
+
+    let manager = PasswordManagerBuilder::new()
+        .with_master_password(MASTER_PASSWORD)
+        .with_account(ACCOUNT, "Bees123")
+        .build()
+        .unlock_with_timeout(MASTER_PASSWORD, Duration::ZERO)
+        .expect("Unlocking with correct master password should work");
+
+    assert!(manager.is_expired());
+    assert_eq!(manager.get_password_checked(ACCOUNT), Err(Expired));
+
+    // Unlocking again without a timeout gives a manager that never expires.
+    let manager = manager
+        .lock()
+        .unlock(MASTER_PASSWORD)
+        .expect("Unlocking with correct master password should work");
+    assert!(!manager.is_expired());
+}
+
+/// Ensure merging with the `KeepExisting` strategy adds new accounts but leaves existing passwords alone.
+#[test]
+fn merging_managers_keeping_existing_passwords_works() {
+    let mut manager = PasswordManager::<Unlocked>::from_accounts(
+        "Master Password",
+        [
+            ("test@example.com", "Bees123"),
+            ("person@social.com", "Wasps456"),
+        ],
+    )
+    .edit();
+    let other = PasswordManager::<Unlocked>::from_accounts(
+        "Other Password",
+        [
+            ("test@example.com", "Ants000"),
+            ("me@news.biz", "Hornets789"),
+        ],
+    );
+
+    manager.merge(other, MergeStrategy::KeepExisting);
+
+    assert_eq!(manager.len(), 3);
+    assert_eq!(
+        manager.get_password("test@example.com"),
+        Some("Bees123".into())
+    );
+    assert_eq!(
+        manager.get_password("person@social.com"),
+        Some("Wasps456".into())
+    );
+    assert_eq!(
+        manager.get_password("me@news.biz"),
+        Some("Hornets789".into())
+    );
+}
+
+/// Ensure merging with the `Overwrite` strategy replaces existing passwords and keeps the receiving manager's master password.
+#[test]
+fn merging_managers_overwriting_passwords_works() {
+    const MASTER_PASSWORD: &str = "Master Password";
+    const OTHER_MASTER_PASSWORD: &str = "Other Password";
+
+    let mut manager = PasswordManager::<Unlocked>::from_accounts(
+        MASTER_PASSWORD,
+        [
+            ("test@example.com", "Bees123"),
+            ("person@social.com", "Wasps456"),
+        ],
+    )
+    .edit();
+    let other = PasswordManager::<Unlocked>::from_accounts(
+        OTHER_MASTER_PASSWORD,
+        [
+            ("test@example.com", "Ants000"),
+            ("me@news.biz", "Hornets789"),
+        ],
+    );
+
+    manager.merge(other, MergeStrategy::Overwrite);
+
+    assert_eq!(manager.len(), 3);
+    assert_eq!(
+        manager.get_password("test@example.com"),
+        Some("Ants000".into())
+    );
+    assert_eq!(
+        manager.get_password("person@social.com"),
+        Some("Wasps456".into())
+    );
+    assert_eq!(
+        manager.get_password("me@news.biz"),
+        Some("Hornets789".into())
+    );
+
+    let manager = manager
+        .mark_saved()
+        .lock()
+        .unlock(OTHER_MASTER_PASSWORD)
+        .expect_err("The merged in master password should be discarded");
+    assert!(manager.unlock(MASTER_PASSWORD).is_ok());
+}
+
+/// Ensure merging managers with no accounts in common keeps every account from both.
+#[test]
+fn merging_disjoint_managers_keeps_every_account() {
+    for strategy in [MergeStrategy::KeepExisting, MergeStrategy::Overwrite] {
+        let mut manager = PasswordManager::<Unlocked>::from_accounts(
+            "Master Password",
+            [("test@example.com", "Bees123")],
+        )
+        .edit();
+        let other = PasswordManager::<Unlocked>::from_accounts(
+            "Other Password",
+            [("me@news.biz", "Hornets789")],
+        );
+
+        manager.merge(other, strategy);
+
+        assert_eq!(manager.len(), 2);
+        assert_eq!(
+            manager.get_password("test@example.com"),
+            Some("Bees123".into())
+        );
+        assert_eq!(
+            manager.get_password("me@news.biz"),
+            Some("Hornets789".into())
+        );
+    }
+}
+
+/// Ensure exporting a manager to CSV and importing it again gives back the same accounts and passwords, even when they need quoting.
+#[test]
+fn csv_round_trip_works() {
+    const MASTER_PASSWORD: &str = "Master Password";
+    const ACCOUNTS: [(&str, &str); 4] = [
+        ("test@example.com", "Bees123"),
+        ("Smith, John", "Wasps,456"),
+        ("\"Quoted\" account", "Say \"hello\""),
+        ("multi\nline", ""),
+    ];
+
+    let manager = PasswordManager::<Unlocked>::from_accounts(MASTER_PASSWORD, ACCOUNTS);
+    let csv = manager.export_csv();
+
+    let imported = PasswordManager::<Unlocked>::from_csv(MASTER_PASSWORD, &csv)
+        .expect("Importing exported CSV should work");
+
+    assert_eq!(imported.get_passwords(), manager.get_passwords());
+    assert!(imported.lock().unlock(MASTER_PASSWORD).is_ok());
+}
+
+/// Ensure importing CSV handles quoted fields and reports malformed input.
+#[test]
+fn importing_csv_works() {
+    const MASTER_PASSWORD: &str = "Master Password";
+
+    let manager = PasswordManager::<Unlocked>::from_csv(
+        MASTER_PASSWORD,
+        "test@example.com,Bees123\r\n\n\"Smith, John\",\"a \"\"b\"\"\"\n",
+    )
+    .expect("Importing valid CSV should work");
+    assert_eq!(manager.len(), 2);
+    assert_eq!(manager.get_password("Smith, John"), Some("a \"b\"".into()));
+
+    let error = PasswordManager::<Unlocked>::from_csv(MASTER_PASSWORD, "a,b\nc,\"d")
+        .expect_err("Importing an unterminated quote should fail");
+    assert_eq!(error, CsvError::UnterminatedQuote { line: 2 });
+
+    let error = PasswordManager::<Unlocked>::from_csv(MASTER_PASSWORD, "a,b,c\n")
+        .expect_err("Importing too many fields should fail");
+    assert_eq!(error, CsvError::WrongFieldCount { line: 1, fields: 3 });
+
+    let manager = PasswordManager::<Unlocked>::from_csv(MASTER_PASSWORD, "a,\"b\"\r\n")
+        .expect("A quoted field should be able to end a CRLF line");
+    assert_eq!(manager.get_password("a"), Some("b".into()));
+
+    let error = PasswordManager::<Unlocked>::from_csv(MASTER_PASSWORD, "a,\"b\"\rc\n")
+        .expect_err("A lone carriage return after a quoted field should fail");
+    assert_eq!(
+        error,
+        CsvError::UnexpectedCharacter {
+            line: 1,
+            character: '\r'
+        }
+    );
+}
+
+/// Ensure a manager built with case-insensitive accounts matches account names regardless of case.
+#[test]
+fn case_insensitive_accounts_match_any_case() {
+    const MASTER_PASSWORD: &str = "Master Password";
+
+    let mut manager = PasswordManagerBuilder::new()
+        .with_master_password(MASTER_PASSWORD)
+        .with_account("Test@Example.com", "Bees123")
+        .with_case_insensitive_accounts()
+        .with_account("Person@Social.com", "Wasps456")
+        .build()
+        .unlock(MASTER_PASSWORD)
+        .expect("Unlocking with correct master password should work")
+        .insert("ME@NEWS.BIZ", "Hornets789");
+
+    assert_eq!(
+        manager.get_password("test@example.com"),
+        Some("Bees123".into())
+    );
+    assert_eq!(
+        manager.get_password("PERSON@social.com"),
+        Some("Wasps456".into())
+    );
+    assert!(manager.contains_account("Me@News.Biz"));
+    assert_eq!(
+        manager.remove_account("me@news.biz"),
+        Some("Hornets789".into())
+    );
+    assert_eq!(manager.len(), 2);
+
+    // The setting survives locking and unlocking again.
+    let manager = manager
+        .mark_saved()
+        .lock()
+        .unlock(MASTER_PASSWORD)
+        .expect("Unlocking with correct master password should work");
+    assert!(manager.contains_account("TEST@EXAMPLE.COM"));
+}
+
+/// Ensure a manager built with case-insensitive accounts still lists and exports each account with the name it was first given.
+#[test]
+fn case_insensitive_accounts_keep_original_names() {
+    const MASTER_PASSWORD: &str = "Master Password";
+
+    let mut manager = PasswordManagerBuilder::new()
+        .with_master_password(MASTER_PASSWORD)
+        .with_account("Test@Example.com", "Bees123")
+        .with_case_insensitive_accounts()
+        .build()
+        .unlock(MASTER_PASSWORD)
+        .expect("Unlocking with correct master password should work")
+        .insert("Person@Social.com", "Wasps456");
+    // Replacing the password of an existing account keeps the name it was first given.
+    manager.insert("TEST@EXAMPLE.COM", "Hornets789");
+
+    assert_eq!(
+        manager.account_names_sorted(),
+        ["Person@Social.com", "Test@Example.com"]
+    );
+    assert_eq!(
+        manager.get_password_ref("test@example.com"),
+        Some("Hornets789")
+    );
+    assert!(manager
+        .export_csv()
+        .contains("Test@Example.com,Hornets789\n"));
+}
+
+/// Ensure account names are matched case-sensitively by default.
+#[test]
+fn accounts_are_case_sensitive_by_default() {
+    const MASTER_PASSWORD: &str = "Master Password";
+
+    let manager = PasswordManagerBuilder::new()
+        .with_master_password(MASTER_PASSWORD)
+        .with_account("Test@Example.com", "Bees123")
+        .build()
+        .unlock(MASTER_PASSWORD)
+        .expect("Unlocking with correct master password should work");
+
+    assert_eq!(
+        manager.get_password("Test@Example.com"),
+        Some("Bees123".into())
+    );
+    assert_eq!(manager.get_password("test@example.com"), None);
+    assert!(!manager.contains_account("test@example.com"));
+}
+
+/// Ensure formatting a secret string never prints the password, even when it is returned from a manager.
+#[test]
+fn formatting_secret_string_hides_password() {
+    const MASTER_PASSWORD: &str = "Master Password";
+    const ACCOUNT: &str = "test@example.com";
+    const PASSWORD: &str = "Bees123";
+
+    let manager =
+        PasswordManager::<Unlocked>::from_accounts(MASTER_PASSWORD, [(ACCOUNT, PASSWORD)]);
+    let password = manager
+        .get_password(ACCOUNT)
+        .expect("Account should be present");
+
+    assert_eq!(format!("{password:?}"), "***");
+    assert_eq!(format!("{password}"), "***");
+    assert!(!format!("{:?}", manager.get_passwords()).contains(PASSWORD));
+    assert_eq!(password.expose(), PASSWORD);
+}
+
+/// Ensure secret strings compare equal only when their passwords match exactly, including in length.
+#[test]
+fn comparing_secret_strings_works() {
+    assert_eq!(SecretString::from("Bees123"), SecretString::from("Bees123"));
+    assert_ne!(SecretString::from("Bees123"), SecretString::from("Bees124"));
+    assert_ne!(
+        SecretString::from("Bees123"),
+        SecretString::from("Bees1234")
+    );
+    assert_ne!(SecretString::from(""), SecretString::from("\0"));
+    assert_eq!(SecretString::default(), SecretString::from(""));
+}
+
+/// Ensure unlocking by reference leaves the locked manager usable after both a failed and a successful attempt.
+#[test]
+fn unlocking_by_reference_keeps_locked_manager() {
+    const MASTER_PASSWORD: &str = "Master Password";
+    const ACCOUNT: &str = "test@example.com";
+    const PASSWORD: &str = "Bees123";
+
+    let mut manager = PasswordManagerBuilder::new()
+        .with_master_password(MASTER_PASSWORD)
+        .with_max_attempts(3)
+        .with_account(ACCOUNT, PASSWORD)
+        .build();
+
+    assert!(manager.unlock_ref("Not Master Password").is_none());
+    assert_eq!(manager.attempts_remaining(), Some(2));
+
+    let unlocked = manager
+        .unlock_ref(MASTER_PASSWORD)
+        .expect("Unlocking with correct master password should work");
+    assert_eq!(unlocked.get_password(ACCOUNT), Some(PASSWORD.into()));
+    assert_eq!(manager.attempts_remaining(), Some(3));
+
+    let unlocked = manager
+        .unlock(MASTER_PASSWORD)
+        .expect("The original manager should still unlock");
+    assert_eq!(unlocked.get_password(ACCOUNT), Some(PASSWORD.into()));
+}
+
+/// Ensure accounts can be filtered by their tags, including tags added after building.
+#[test]
+fn filtering_accounts_by_tag_works() {
+    const MASTER_PASSWORD: &str = "Master Password";
+
+    let mut manager = PasswordManagerBuilder::new()
+        .with_master_password(MASTER_PASSWORD)
+        .with_account_entry(
+            "test@example.com",
+            AccountEntry {
+                url: Some(String::from("https://example.com")),
+                tags: vec![String::from("work")],
+                ..AccountEntry::new("Bees123")
+            },
+        )
+        .with_account("person@social.com", "Wasps456")
+        .with_account("me@news.biz", "Hornets789")
+        .build()
+        .unlock(MASTER_PASSWORD)
+        .expect("Unlocking with correct master password should work")
+        .edit();
+
+    assert!(manager.add_tag("me@news.biz", "work"));
+    assert!(manager.add_tag("me@news.biz", "work"));
+    assert!(!manager.add_tag("nobody@nowhere.org", "work"));
+
+    let mut work: Vec<&String> = manager.accounts_with_tag("work").collect();
+    work.sort();
+    assert_eq!(work, ["me@news.biz", "test@example.com"]);
+    assert_eq!(manager.accounts_with_tag("banking").count(), 0);
+
+    let entry = manager
+        .get_entry("me@news.biz")
+        .expect("Account should be present");
+    assert_eq!(entry.tags, ["work"]);
+    assert_eq!(
+        manager.get_password("test@example.com"),
+        Some("Bees123".into())
+    );
+}
+
+/// Ensure an account's notes can be updated without touching its password or other fields.
+#[test]
+fn updating_account_notes_works() {
+    const MASTER_PASSWORD: &str = "Master Password";
+    const ACCOUNT: &str = "test@example.com";
+
+    let mut manager = PasswordManagerBuilder::new()
+        .with_master_password(MASTER_PASSWORD)
+        .with_account_entry(
+            ACCOUNT,
+            AccountEntry {
+                url: Some(String::from("https://example.com")),
+                ..AccountEntry::new("Bees123")
+            },
+        )
+        .build()
+        .unlock(MASTER_PASSWORD)
+        .expect("Unlocking with correct master password should work")
+        .edit();
+
+    assert!(manager.set_notes(ACCOUNT, Some(String::from("Security question: bees"))));
+    // Replacing the password keeps the rest of the entry.
+    manager.insert(ACCOUNT, "Wasps456");
+
+    let entry = manager
+        .get_entry(ACCOUNT)
+        .expect("Account should be present");
+    assert_eq!(entry.notes.as_deref(), Some("Security question: bees"));
+    assert_eq!(entry.url.as_deref(), Some("https://example.com"));
+    assert_eq!(entry.password.expose(), "Wasps456");
+
+    assert!(manager.set_notes(ACCOUNT, None));
+    assert_eq!(
+        manager
+            .get_entry(ACCOUNT)
+            .and_then(|entry| entry.notes.clone()),
+        None
+    );
+    assert!(!manager.set_notes("nobody@nowhere.org", None));
+}
+
+/// Ensure searching finds the accounts containing the query regardless of case, sorted alphabetically.
+#[test]
+fn searching_accounts_works() {
+    let manager = PasswordManager::<Unlocked>::from_accounts(
+        "Master Password",
+        [
+            ("test@example.com", "Bees123"),
+            ("Person@Example.org", "Wasps456"),
+            ("me@news.biz", "Hornets789"),
+            ("admin@EXAMPLE.com", "Ants000"),
+        ],
+    );
+
+    assert_eq!(
+        manager.search("example"),
+        [
+            "admin@EXAMPLE.com",
+            "Person@Example.org",
+            "test@example.com"
+        ]
+    );
+    assert_eq!(manager.search("NEWS"), ["me@news.biz"]);
+    assert!(manager.search("social").is_empty());
+}
+
+/// Ensure searching only ever matches and returns account names, never passwords.
+#[test]
+fn searching_accounts_never_returns_passwords() {
+    let manager = PasswordManager::<Unlocked>::from_accounts(
+        "Master Password",
+        [
+            ("test@example.com", "Bees123"),
+            ("me@news.biz", "Hornets789"),
+        ],
+    );
+
+    assert!(manager.search("Bees").is_empty());
+    for account in manager.search("") {
+        assert!(!account.contains("Bees123"));
+        assert!(!account.contains("Hornets789"));
+    }
+    assert_eq!(manager.search("").len(), 2);
+}
+
+/// Ensure a read-only manager can read accounts and passwords and be locked again.  That it can't change them is checked by the compile-fail
+/// examples on [ReadOnly](crate::ReadOnly).
+#[test]
+fn read_only_manager_can_read_passwords() {
+    const MASTER_PASSWORD: &str = "Master Password";
+    const ACCOUNT: &str = "test@example.com";
+    const PASSWORD: &str = "Bees123";
+
+    let manager = PasswordManagerBuilder::new()
+        .with_master_password(MASTER_PASSWORD)
+        .with_account(ACCOUNT, PASSWORD)
+        .build();
+    let manager = manager
+        .unlock_read_only(format!("Not {MASTER_PASSWORD}"))
+        .expect_err("Unlocking with incorrect master password should fail");
+    let manager = manager
+        .unlock_read_only(MASTER_PASSWORD)
+        .expect("Unlocking with correct master password should work");
+
+    assert_eq!(manager.get_password(ACCOUNT), Some(PASSWORD.into()));
+    assert_eq!(manager.iter().collect::<Vec<_>>(), [(ACCOUNT, PASSWORD)]);
+    assert!(manager.contains_account(ACCOUNT));
+    assert_eq!(manager.to_string(), "PasswordManager(read-only, 1 account)");
+
+    assert!(manager.lock().unlock(MASTER_PASSWORD).is_ok());
+}
+
+/// Ensure overwriting an account's password records the old ones from oldest to most recent.
+#[test]
+fn overwriting_password_records_history() {
+    const MASTER_PASSWORD: &str = "Master Password";
+    const ACCOUNT: &str = "test@example.com";
+
+    let mut manager =
+        PasswordManager::<Unlocked>::from_accounts(MASTER_PASSWORD, [(ACCOUNT, "Bees123")]);
+    assert_eq!(manager.password_history(ACCOUNT), Some(&[][..]));
+
+    let mut manager = manager.insert(ACCOUNT, "Wasps456");
+    manager.insert(ACCOUNT, "Hornets789");
+
+    assert_eq!(
+        manager.password_history(ACCOUNT),
+        Some(
+            &[
+                SecretString::from("Bees123"),
+                SecretString::from("Wasps456")
+            ][..]
+        )
+    );
+    assert_eq!(manager.get_password(ACCOUNT), Some("Hornets789".into()));
+    assert_eq!(manager.password_history("me@news.biz"), None);
+}
+
+/// Ensure reusing an old password is detected but the current password and unused ones aren't reported.
+#[test]
+fn reusing_old_password_is_detected() {
+    const MASTER_PASSWORD: &str = "Master Password";
+    const ACCOUNT: &str = "test@example.com";
+
+    let mut manager =
+        PasswordManager::<Unlocked>::from_accounts(MASTER_PASSWORD, [(ACCOUNT, "Bees123")]);
+    let mut manager = manager.insert(ACCOUNT, "Wasps456");
+
+    assert!(manager.was_previously_used(ACCOUNT, "Bees123"));
+    assert!(!manager.was_previously_used(ACCOUNT, "Wasps456"));
+    assert!(!manager.was_previously_used(ACCOUNT, "Hornets789"));
+    assert!(!manager.was_previously_used("me@news.biz", "Bees123"));
+}
+
+/// Ensure turning an unlocked manager back into a builder and building it again keeps every account and the master password.
+#[test]
+fn rebuilding_from_unlocked_manager_keeps_everything() {
+    const MASTER_PASSWORD: &str = "Master Password";
+
+    let manager = PasswordManagerBuilder::new()
+        .with_master_password(MASTER_PASSWORD)
+        .with_max_attempts(3)
+        .with_account_entry(
+            "test@example.com",
+            AccountEntry {
+                notes: Some(String::from("Work account")),
+                ..AccountEntry::new("Bees123")
+            },
+        )
+        .with_account("person@social.com", "Wasps456")
+        .build()
+        .unlock(MASTER_PASSWORD)
+        .expect("Unlocking with correct master password should work");
+    let original = manager.get_passwords();
+
+    let manager = PasswordManagerBuilder::from_unlocked(manager)
+        .with_account("me@news.biz", "Hornets789")
+        .build();
+    assert_eq!(manager.attempts_remaining(), Some(3));
+
+    let manager = manager
+        .unlock(MASTER_PASSWORD)
+        .expect("Unlocking with the original master password should work");
+    assert_eq!(manager.len(), 3);
+    for (account, password) in original {
+        assert_eq!(manager.get_password(&account), Some(password));
+    }
+    assert_eq!(
+        manager.get_password("me@news.biz"),
+        Some("Hornets789".into())
+    );
+    assert_eq!(
+        manager
+            .get_entry("test@example.com")
+            .and_then(|entry| entry.notes.as_deref()),
+        Some("Work account")
+    );
+}
+
+/// Ensure a builder created with a capacity gives a manager with room for at least that many accounts, and that reserving adds more.
+#[test]
+fn building_with_capacity_preallocates() {
+    const MASTER_PASSWORD: &str = "Master Password";
+
+    let mut manager = PasswordManagerBuilder::with_capacity(100)
+        .with_master_password(MASTER_PASSWORD)
+        .build()
+        .unlock(MASTER_PASSWORD)
+        .expect("Unlocking with correct master password should work")
+        .edit();
+    assert!(manager.capacity() >= 100);
+
+    for i in 0..100 {
+        manager.insert(format!("account{i}"), "Bees123");
+    }
+    manager.reserve(50);
+    assert!(manager.capacity() >= 150);
+}
+
+/// Ensure borrowing a password gives the same value as cloning it.
+#[test]
+fn borrowing_password_matches_cloning_it() {
+    const ACCOUNTS: [(&str, &str); 2] = [
+        ("test@example.com", "Bees123"),
+        ("person@social.com", "Wasps456"),
+    ];
+
+    let manager = PasswordManager::<Unlocked>::from_accounts("Master Password", ACCOUNTS);
+
+    for (account, password) in ACCOUNTS {
+        let owned = manager.get_password(account);
+        assert_eq!(manager.get_password_ref(account), Some(password));
+        assert_eq!(owned.as_ref().map(SecretString::expose), Some(password));
+    }
+    assert_eq!(manager.get_password_ref("me@news.biz"), None);
+}
+
+/// Ensure indexing an unlocked manager by account gives its password.
+#[test]
+fn indexing_manager_by_account_works() {
+    let manager = PasswordManager::<Unlocked>::from_accounts(
+        "Master Password",
+        [
+            ("test@example.com", "Bees123"),
+            ("me@news.biz", "Hornets789"),
+        ],
+    );
+
+    assert_eq!(&manager["test@example.com"], "Bees123");
+    assert_eq!(&manager["me@news.biz"], "Hornets789");
+}
+
+/// Ensure indexing an unlocked manager by a missing account panics.
+#[test]
+#[should_panic(expected = "account not found")]
+fn indexing_manager_by_missing_account_panics() {
+    let manager = PasswordManager::<Unlocked>::from_accounts(
+        "Master Password",
+        [("test@example.com", "Bees123")],
+    );
+
+    let _ = &manager["me@news.biz"];
+}
+
+/// Ensure clearing a manager removes every account but keeps its master password.
+#[test]
+fn clearing_manager_keeps_master_password() {
+    const MASTER_PASSWORD: &str = "Master Password";
+
+    let mut manager = PasswordManager::<Unlocked>::from_accounts(
+        MASTER_PASSWORD,
+        [
+            ("test@example.com", "Bees123"),
+            ("me@news.biz", "Hornets789"),
+        ],
+    );
+    let mut manager = manager.insert("person@social.com", "Wasps456");
+
+    manager.clear();
+    assert_eq!(manager.len(), 0);
+
+    let manager = manager
+        .mark_saved()
+        .lock()
+        .unlock(MASTER_PASSWORD)
+        .expect("Unlocking with the original master password should work");
+    assert!(manager.is_empty());
+}
+
+/// Ensure clearing a manager zeroizes the removed passwords.
+#[cfg(feature = "zeroize")]
+#[test]
+fn clearing_manager_zeroizes_passwords() {
+    let drops = std::rc::Rc::new(std::cell::RefCell::new(Vec::new()));
+    let recorder = || DropRecorder {
+        zeroized: false,
+        drops: drops.clone(),
+    };
+
+    let mut manager: PasswordManager<Unlocked<Dirty>, String, DropRecorder> =
+        PasswordManager::<Unlocked, String, DropRecorder>::from_accounts(
+            "Master Password",
+            [("First", recorder()), ("Second", recorder())],
+        )
+        .edit();
+
+    manager.clear();
+    assert_eq!(*drops.borrow(), vec![true, true]);
+}
+
+/// Ensure removing several accounts at once returns only the ones that existed.
+#[test]
+fn removing_several_accounts() {
+    let mut manager = PasswordManager::<Unlocked>::from_accounts(
+        "Master Password",
+        [
+            ("test@example.com", "Bees123"),
+            ("person@social.com", "Wasps456"),
+            ("me@news.biz", "Hornets789"),
+        ],
+    )
+    .edit();
+
+    let removed =
+        manager.remove_accounts(["test@example.com", "missing@example.com", "me@news.biz"]);
+
+    assert_eq!(removed.len(), 2);
+    assert_eq!(removed["test@example.com"], *"Bees123");
+    assert_eq!(removed["me@news.biz"], *"Hornets789");
+    assert_eq!(manager.len(), 1);
+    assert!(manager.contains_account("person@social.com"));
+}
+
+/// Ensure retaining accounts keeps only the ones the predicate accepts.
+#[test]
+fn retaining_accounts() {
+    let mut manager = PasswordManager::<Unlocked>::from_accounts(
+        "Master Password",
+        [
+            ("test@example.com", "Bees123"),
+            ("person@social.com", "Wasps456"),
+            ("me@news.biz", "Hornets789"),
+        ],
+    )
+    .edit();
+
+    manager.retain(|account, _| account.ends_with(".com"));
+
+    let mut accounts: Vec<_> = manager.accounts().collect();
+    accounts.sort();
+    assert_eq!(accounts, ["person@social.com", "test@example.com"]);
+}
+
+/// Ensure `or_insert` only adds a password for accounts that don't exist yet.
+#[test]
+fn entry_or_insert_adds_missing_accounts() {
+    let mut manager = PasswordManager::<Unlocked>::from_accounts(
+        "Master Password",
+        [("test@example.com", "Bees123")],
+    )
+    .edit();
+
+    assert_eq!(
+        *manager.entry("test@example.com").or_insert("Wasps456"),
+        *"Bees123"
+    );
+    assert_eq!(
+        *manager.entry("me@news.biz").or_insert("Hornets789"),
+        *"Hornets789"
+    );
+
+    assert_eq!(manager.len(), 2);
+    assert_eq!(
+        manager.get_password("me@news.biz"),
+        Some("Hornets789".into())
+    );
+}
+
+/// Ensure `and_modify` changes the password of an existing account and is skipped for a missing one.
+#[test]
+fn entry_and_modify_changes_existing_accounts() {
+    let mut manager = PasswordManager::<Unlocked>::from_accounts(
+        "Master Password",
+        [("test@example.com", "Bees123")],
+    )
+    .edit();
+
+    for account in ["test@example.com", "me@news.biz"] {
+        manager
+            .entry(account)
+            .and_modify(|password| password.expose_mut().push('!'))
+            .or_insert("Hornets789");
+    }
+
+    assert_eq!(
+        manager.get_password("test@example.com"),
+        Some("Bees123!".into())
+    );
+    assert_eq!(
+        manager.get_password("me@news.biz"),
+        Some("Hornets789".into())
+    );
+}
+
+/// Ensure getting several passwords at once includes every account asked for, whether or not it exists.
+#[test]
+fn getting_many_passwords() {
+    let manager = PasswordManager::<Unlocked>::from_accounts(
+        "Master Password",
+        [
+            ("test@example.com", "Bees123"),
+            ("me@news.biz", "Hornets789"),
+        ],
+    );
+
+    let passwords = manager.get_many(["test@example.com", "missing@example.com", "me@news.biz"]);
+
+    assert_eq!(passwords.len(), 3);
+    assert_eq!(passwords["test@example.com"], Some("Bees123".into()));
+    assert_eq!(passwords["missing@example.com"], None);
+    assert_eq!(passwords["me@news.biz"], Some("Hornets789".into()));
+}
+
+/// Ensure inserting an account whose name is only whitespace panics rather than storing it.
+#[test]
+#[should_panic(expected = "Account names can't be empty or only whitespace")]
+fn inserting_blank_account_panics() {
+    let manager = PasswordManager::<Unlocked>::from_accounts(
+        "Master Password",
+        [("test@example.com", "Bees123")],
+    );
+
+    let _ = manager.insert("  ", "Wasps456");
+}
+
+/// Ensure `try_insert` refuses empty and whitespace-only account names without changing the manager.
+#[test]
+fn try_inserting_blank_account_fails() {
+    let mut manager = PasswordManager::<Unlocked>::from_accounts(
+        "Master Password",
+        [("test@example.com", "Bees123")],
+    )
+    .edit();
+
+    assert_eq!(
+        manager.try_insert("", "Wasps456"),
+        Err(InsertError::EmptyAccount)
+    );
+    assert_eq!(
+        manager.try_insert(" \t\n", "Wasps456"),
+        Err(InsertError::EmptyAccount)
+    );
+    assert_eq!(manager.len(), 1);
+}
+
+/// Ensure `try_insert` inserts accounts with non-blank names.
+#[test]
+fn try_inserting_valid_account_works() {
+    let mut manager = PasswordManager::<Unlocked>::from_accounts(
+        "Master Password",
+        [("test@example.com", "Bees123")],
+    )
+    .edit();
+
+    assert_eq!(manager.try_insert(" me@news.biz ", "Hornets789"), Ok(()));
+    assert_eq!(manager.try_insert("test@example.com", "Wasps456"), Ok(()));
+
+    assert_eq!(
+        manager.get_password(" me@news.biz "),
+        Some("Hornets789".into())
+    );
+    assert_eq!(
+        manager.get_password("test@example.com"),
+        Some("Wasps456".into())
+    );
+}
+
+/// Ensure renaming an account moves its password to the new name.
+#[test]
+fn renaming_account_works() {
+    const MASTER_PASSWORD: &str = "Master Password";
+
+    let mut manager = PasswordManagerBuilder::new()
+        .with_master_password(MASTER_PASSWORD)
+        .with_account("test@exmaple.com", "Bees123")
+        .build()
+        .unlock(MASTER_PASSWORD)
+        .expect("Unlocking with correct master password should work")
+        .edit();
+
+    assert!(manager.rename_account("test@exmaple.com", "test@example.com"));
+    assert_eq!(manager.get_password("test@exmaple.com"), None);
+    assert_eq!(
+        manager.get_password("test@example.com"),
+        Some(SecretString::from("Bees123"))
+    );
+}
+
+/// Ensure renaming an account that doesn't exist does nothing.
+#[test]
+fn renaming_missing_account_fails() {
+    const MASTER_PASSWORD: &str = "Master Password";
+
+    let mut manager = PasswordManagerBuilder::new()
+        .with_master_password(MASTER_PASSWORD)
+        .with_account("test@example.com", "Bees123")
+        .build()
+        .unlock(MASTER_PASSWORD)
+        .expect("Unlocking with correct master password should work")
+        .edit();
+
+    assert!(!manager.rename_account("Not an Account", "test@example.com"));
+    assert_eq!(
+        manager.get_password("test@example.com"),
+        Some(SecretString::from("Bees123"))
+    );
+    assert_eq!(manager.len(), 1);
+}
+
+/// Ensure renaming an account onto an existing account overwrites the existing password.
+#[test]
+fn renaming_account_onto_existing_account_overwrites_it() {
+    const MASTER_PASSWORD: &str = "Master Password";
+
+    let mut manager = PasswordManagerBuilder::new()
+        .with_master_password(MASTER_PASSWORD)
+        .with_account("test@example.com", "Bees123")
+        .with_account("person@social.com", "Wasps456")
+        .build()
+        .unlock(MASTER_PASSWORD)
+        .expect("Unlocking with correct master password should work")
+        .edit();
+
+    assert!(manager.rename_account("test@example.com", "person@social.com"));
+    assert_eq!(
+        manager.get_password("person@social.com"),
+        Some(SecretString::from("Bees123"))
+    );
+    assert_eq!(manager.len(), 1);
+}
+
+/// Ensure a manager with the hashing feature doesn't keep the plaintext master password around.
+#[cfg(feature = "hashing")]
+#[test]
+fn hashed_manager_does_not_store_plaintext_master_password() {
+    const MASTER_PASSWORD: &str = "Master Password";
+
+    let manager = PasswordManagerBuilder::new()
+        .with_master_password(MASTER_PASSWORD)
+        .build();
+
+    assert!(!format!("{manager:?}").contains(MASTER_PASSWORD));
+}
+
+/// Test that a manager built with a custom hasher stores and looks up accounts the same as one using the default hasher, across locking.
+#[test]
+fn building_with_custom_hasher_works() {
+    use std::collections::hash_map::DefaultHasher;
+    use std::hash::BuildHasherDefault;
+
+    const MASTER_PASSWORD: &str = "Master Password";
+
+    let manager: PasswordManager<Locked, String, SecretString, BuildHasherDefault<DefaultHasher>> =
+        PasswordManagerBuilder::new()
+            .with_account("test@example.com", "Bees123")
+            .with_hasher()
+            .with_account("other@example.com", "Wasps456")
+            .with_master_password(MASTER_PASSWORD)
+            .build();
+
+    let manager = manager
+        .unlock(MASTER_PASSWORD)
+        .expect("Unlocking with correct master password should work")
+        .lock();
+    let unlocked = manager
+        .unlock(MASTER_PASSWORD)
+        .expect("Unlocking with correct master password should work");
+    assert_eq!(
+        unlocked.get_password("test@example.com"),
+        Some("Bees123".into())
+    );
+    assert_eq!(unlocked.len(), 2);
+}
+
+/// Test that replacing an existing account's password returns the old one and keeps it in the account's history.
+#[test]
+fn replacing_existing_password_returns_old_one() {
+    let mut manager = PasswordManager::<Unlocked>::from_accounts(
+        "Master Password",
+        [("test@example.com", "Bees123")],
+    )
+    .edit();
+
+    assert_eq!(
+        manager.replace_password("test@example.com", "Wasps456"),
+        Ok("Bees123".to_string())
+    );
+    assert_eq!(
+        manager.get_password_ref("test@example.com"),
+        Some("Wasps456")
+    );
+    assert_eq!(
+        manager.password_history("test@example.com").unwrap(),
+        [SecretString::from("Bees123")]
+    );
+}
+
+/// Test that replacing the password of an account that doesn't exist fails without adding the account.
+#[test]
+fn replacing_missing_password_fails() {
+    let mut manager = PasswordManager::<Unlocked>::from_accounts(
+        "Master Password",
+        [("test@example.com", "Bees123")],
+    )
+    .edit();
+
+    assert_eq!(
+        manager.replace_password("tset@example.com", "Wasps456"),
+        Err(AccountNotFound {
+            account: "tset@example.com".to_string()
+        })
+    );
+    assert!(!manager.contains_account("tset@example.com"));
+    assert_eq!(manager.len(), 1);
+}
+
+/// Test that locked managers are equal to their clones but not to managers with other accounts.
+#[test]
+fn comparing_locked_managers_compares_contents() {
+    let manager = PasswordManagerBuilder::new()
+        .with_master_password("Master Password")
+        .with_account("test@example.com", "Bees123")
+        .build();
+    let other = PasswordManagerBuilder::new()
+        .with_master_password("Master Password")
+        .with_account("test@example.com", "Wasps456")
+        .build();
+
+    assert_eq!(manager, manager.clone());
+    assert_ne!(manager, other);
+}
+
+/// Test that unlocked managers with the same master password and accounts are equal, and stop being equal once their accounts differ.
+#[test]
+fn comparing_unlocked_managers_compares_contents() {
+    let manager = PasswordManagerBuilder::new()
+        .with_master_password("Master Password")
+        .with_account("test@example.com", "Bees123")
+        .build();
+    // Unlocking a clone keeps the master password's salt the same when it is hashed.
+    let first = manager.clone().unlock("Master Password").unwrap();
+    let second = manager.unlock("Master Password").unwrap();
+
+    assert_eq!(first, second);
+    let second = second.insert("other@example.com", "Wasps456").mark_saved();
+    assert_ne!(first, second);
+}
+
+/// Test that building with a policy fails when the master password is rejected by it.
+#[test]
+fn building_with_policy_rejects_short_password() {
+    let result = PasswordManagerBuilder::new()
+        .with_master_password("Short")
+        .try_build_with_policy(&MinLengthPolicy(12));
+
+    assert_eq!(
+        result.unwrap_err(),
+        PolicyError::new("the password is 5 characters long but must be at least 12")
+    );
+}
+
+/// Test that building with a policy gives a manager that unlocks when the policy accepts the master password, including a custom policy.
+#[test]
+fn building_with_policy_accepts_valid_password() {
+    struct NoSpaces;
+
+    impl PasswordPolicy for NoSpaces {
+        fn validate(&self, pw: &str) -> Result<(), PolicyError> {
+            match pw.contains(' ') {
+                true => Err(PolicyError::new("the password contains a space")),
+                false => Ok(()),
+            }
+        }
+    }
+
+    let manager = PasswordManagerBuilder::new()
+        .with_master_password("Long-Master-Password")
+        .try_build_with_policy(&MinLengthPolicy(12))
+        .unwrap();
+    assert!(manager.unlock("Long-Master-Password").is_ok());
+
+    let result = PasswordManagerBuilder::new()
+        .with_master_password("Master Password")
+        .try_build_with_policy(&NoSpaces);
+    assert!(result.is_err());
+}
+
+/// Test that account names are returned sorted no matter what order they were inserted in.
+#[test]
+fn account_names_sorted_are_sorted() {
+    let manager = PasswordManager::<Unlocked>::from_accounts(
+        "Master Password",
+        [
+            ("zebra@example.com", "Bees123"),
+            ("Apple@example.com", "Wasps456"),
+            ("mango@example.com", "Ants789"),
+            ("apple@example.com", "Flies012"),
+        ],
+    );
+
+    assert_eq!(
+        manager.account_names_sorted(),
+        [
+            "Apple@example.com",
+            "apple@example.com",
+            "mango@example.com",
+            "zebra@example.com"
+        ]
+    );
+}
+
+/// Test that verifying the correct master password succeeds without unlocking the manager or changing its attempts.
+#[test]
+fn verifying_correct_password_leaves_manager_locked() {
+    let manager = PasswordManagerBuilder::new()
+        .with_master_password("Master Password")
+        .with_max_attempts(3)
+        .build();
+
+    assert!(manager.verify("Master Password"));
+    assert_eq!(manager.attempts_remaining(), Some(3));
+    assert_eq!(manager.failed_attempts(), 0);
+    assert!(manager.unlock("Master Password").is_ok());
+}
+
+/// Test that verifying a wrong master password fails without using up an attempt.
+#[test]
+fn verifying_incorrect_password_does_not_use_attempts() {
+    let manager = PasswordManagerBuilder::new()
+        .with_master_password("Master Password")
+        .with_max_attempts(3)
+        .build();
+
+    assert!(!manager.verify("Wrong Password"));
+    assert_eq!(manager.attempts_remaining(), Some(3));
+    assert_eq!(manager.failed_attempts(), 0);
+}
+
+/// Test that inserting a new account only if it is absent inserts it.
+#[test]
+fn inserting_absent_account_inserts_it() {
+    let mut manager = PasswordManager::<Unlocked>::from_accounts(
+        "Master Password",
+        [("test@example.com", "Bees123")],
+    )
+    .edit();
+
+    assert!(manager.insert_if_absent("other@example.com", "Wasps456"));
+    assert_eq!(
+        manager.get_password_ref("other@example.com"),
+        Some("Wasps456")
+    );
+}
+
+/// Test that inserting an existing account only if it is absent leaves its password and history as they were.
+#[test]
+fn inserting_existing_account_if_absent_keeps_old_password() {
+    let mut manager = PasswordManager::<Unlocked>::from_accounts(
+        "Master Password",
+        [("test@example.com", "Bees123")],
+    )
+    .edit();
+
+    assert!(!manager.insert_if_absent("test@example.com", "Wasps456"));
+    assert_eq!(
+        manager.get_password_ref("test@example.com"),
+        Some("Bees123")
+    );
+    assert!(manager
+        .password_history("test@example.com")
+        .unwrap()
+        .is_empty());
+}
+
+/// Test that accounts loaded from a JSON object are added to the builder alongside its other accounts.
+#[cfg(feature = "json")]
+#[test]
+fn loading_accounts_from_json_object_works() {
+    const MASTER_PASSWORD: &str = "Master Password";
+
+    let manager = PasswordManagerBuilder::new()
+        .with_account("test@example.com", "Bees123")
+        .with_accounts_from_json(
+            r#"{"other@example.com": "Wasps456", "test@example.com": "Ants789"}"#,
+        )
+        .expect("Loading a JSON object of strings should work")
+        .with_master_password(MASTER_PASSWORD)
+        .build()
+        .unlock(MASTER_PASSWORD)
+        .expect("Unlocking with correct master password should work");
+
+    assert_eq!(manager.len(), 2);
+    assert_eq!(
+        manager.get_password_ref("test@example.com"),
+        Some("Ants789")
+    );
+    assert_eq!(
+        manager.get_password_ref("other@example.com"),
+        Some("Wasps456")
+    );
+}
+
+/// Test that JSON which isn't an object of strings is rejected.
+#[cfg(feature = "json")]
+#[test]
+fn loading_accounts_from_nested_json_fails() {
+    use crate::password_manager::JsonError;
+
+    let nested = PasswordManagerBuilder::new()
+        .with_accounts_from_json(r#"{"test@example.com": {"password": "Bees123"}}"#);
+    assert!(matches!(nested, Err(JsonError::UnexpectedStructure { .. })));
+
+    let array = PasswordManagerBuilder::new().with_accounts_from_json(r#"["test@example.com"]"#);
+    assert!(matches!(array, Err(JsonError::UnexpectedStructure { .. })));
+
+    let invalid = PasswordManagerBuilder::new().with_accounts_from_json(r#"{"test@example.com": "#);
+    assert!(matches!(invalid, Err(JsonError::InvalidJson { .. })));
+}
+
+/// Test that empty input isn't treated as an empty object.
+#[cfg(feature = "json")]
+#[test]
+fn loading_accounts_from_empty_json_fails() {
+    use crate::password_manager::JsonError;
+
+    let result = PasswordManagerBuilder::new().with_accounts_from_json("");
+    assert_eq!(
+        result.err(),
+        Some(JsonError::InvalidJson { line: 1, column: 0 })
+    );
+}
+
+/// Test that comparing managers with no accounts in common lists every account as only being in one of them.
+#[test]
+fn difference_of_disjoint_managers_lists_every_account() {
+    let first = PasswordManager::<Unlocked>::from_accounts(
+        "Master Password",
+        [("test@example.com", "Bees123")],
+    );
+    let second = PasswordManager::<Unlocked>::from_accounts(
+        "Master Password",
+        [("other@example.com", "Wasps456")],
+    );
+
+    assert_eq!(
+        first.difference(&second),
+        VaultDiff {
+            only_in_self: vec!["test@example.com".to_string()],
+            only_in_other: vec!["other@example.com".to_string()],
+            changed: vec![],
+        }
+    );
+}
+
+/// Test that accounts stored in both managers with the same password aren't listed, but ones with different passwords are.
+#[test]
+fn difference_lists_only_changed_shared_accounts() {
+    let first = PasswordManager::<Unlocked>::from_accounts(
+        "Master Password",
+        [
+            ("test@example.com", "Bees123"),
+            ("other@example.com", "Wasps456"),
+        ],
+    );
+    let same = PasswordManager::<Unlocked>::from_accounts(
+        "Other Password",
+        [
+            ("test@example.com", "Bees123"),
+            ("other@example.com", "Wasps456"),
+        ],
+    );
+    let changed = PasswordManager::<Unlocked>::from_accounts(
+        "Master Password",
+        [
+            ("test@example.com", "Bees123"),
+            ("other@example.com", "Ants789"),
+        ],
+    );
+
+    let diff = first.difference(&same);
+    assert!(
+        diff.only_in_self.is_empty() && diff.only_in_other.is_empty() && diff.changed.is_empty()
+    );
+    assert_eq!(
+        first.difference(&changed),
+        VaultDiff {
+            only_in_self: vec![],
+            only_in_other: vec![],
+            changed: vec!["other@example.com".to_string()],
+        }
+    );
+}
+
+/// Test that a manager can be unlocked with a custom credential type, and that a credential which doesn't match uses up an attempt.
+#[cfg(not(feature = "hashing"))]
+#[test]
+fn unlocking_with_custom_credential_works() {
+    use crate::password_manager::Credential;
+
+    // A passphrase that ignores the case of its words.
+    struct Passphrase(&'static str);
+
+    impl Credential for Passphrase {
+        fn matches(&self, stored: &str) -> bool {
+            self.0.to_lowercase() == stored.to_lowercase()
+        }
+    }
+
+    let manager = PasswordManagerBuilder::new()
+        .with_master_password("Correct Horse Battery Staple")
+        .with_max_attempts(3)
+        .build();
+
+    let manager = manager
+        .unlock_with(Passphrase("correct horse battery stable"))
+        .unwrap_err();
+    assert_eq!(manager.attempts_remaining(), Some(2));
+    assert!(manager
+        .unlock_with(Passphrase("CORRECT HORSE BATTERY STAPLE"))
+        .is_ok());
+}
+
+/// Test that a PIN credential unlocks a manager whose master password is that PIN, and nothing else.
+#[cfg(not(feature = "hashing"))]
+#[test]
+fn unlocking_with_pin_credential_works() {
+    use crate::password_manager::PinCredential;
+
+    let manager = PasswordManagerBuilder::new()
+        .with_master_password("0042")
+        .build();
+
+    let manager = manager.unlock_with(PinCredential::new("42")).unwrap_err();
+    assert!(manager.unlock_with(PinCredential::new("0042")).is_ok());
+}
+
+/// Test that an account configured through the nested account builder keeps its URL and tags.
+#[test]
+fn building_account_with_nested_builder_works() {
+    const MASTER_PASSWORD: &str = "Master Password";
+
+    let manager = PasswordManagerBuilder::new()
+        .with_master_password(MASTER_PASSWORD)
+        .with_account_using("test@example.com", |account| {
+            account
+                .url("https://example.com")
+                .password("Bees123")
+                .tag("work")
+                .tag("email")
+        })
+        .build()
+        .unlock(MASTER_PASSWORD)
+        .expect("Unlocking with correct master password should work");
+
+    let entry = manager
+        .get_entry("test@example.com")
+        .expect("Account should be present");
+    assert_eq!(entry.password.expose(), "Bees123");
+    assert_eq!(entry.url.as_deref(), Some("https://example.com"));
+    assert_eq!(entry.notes, None);
+    assert_eq!(entry.tags, ["work", "email"]);
+}
+
+/// Test that the master password's length is counted in characters rather than bytes.
+#[cfg(not(feature = "hashing"))]
+#[test]
+fn master_password_len_counts_characters() {
+    let manager = PasswordManagerBuilder::new()
+        .with_master_password("Pässwörd")
+        .build();
+
+    assert_eq!(manager.master_password_len(), 8);
+}
+
+/// Test that setting the master password of a manager that needs setting up gives a normal locked manager.
+#[test]
+fn setting_up_manager_gives_locked_manager() {
+    let manager = PasswordManager::<NeedsSetup>::new();
+    assert!(format!("{manager:?}").contains("NeedsSetup"));
+
+    let manager: PasswordManager<Locked> = manager.set_master_password("Master Password");
+    assert_eq!(manager.account_count(), 0);
+
+    let manager = manager.unlock("Wrong Password").unwrap_err();
+    let mut manager = manager.unlock("Master Password").unwrap();
+    let mut manager = manager.insert("test@example.com", "Bees123");
+    assert_eq!(
+        manager.get_password_ref("test@example.com"),
+        Some("Bees123")
+    );
+}
+
+/// Test that errors can be propagated with `?` through a function returning a boxed error, keeping their message.
+#[test]
+fn errors_propagate_as_boxed_errors() {
+    fn change_password(
+        manager: &mut PasswordManager<Unlocked<Dirty>>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        manager.replace_password("tset@example.com", "Wasps456")?;
+        Ok(())
+    }
+
+    fn build() -> Result<PasswordManager, Box<dyn std::error::Error>> {
+        Ok(PasswordManagerBuilder::new().try_build()?)
+    }
+
+    let mut manager = PasswordManager::<Unlocked>::from_accounts(
+        "Master Password",
+        [("test@example.com", "Bees123")],
+    )
+    .edit();
+    let error = change_password(&mut manager).unwrap_err();
+    assert!(error.is::<AccountNotFound>());
+    assert_eq!(
+        error.to_string(),
+        "the account \"tset@example.com\" doesn't exist"
+    );
+
+    let error = build().unwrap_err();
+    assert_eq!(error.to_string(), "the master password hasn't been set");
+}
+
+/// Test that a bulk update changes every stored password.
+#[test]
+fn bulk_update_changes_every_password() {
+    let mut manager = PasswordManager::<Unlocked>::from_accounts(
+        "Master Password",
+        [
+            ("test@example.com", "Bees123"),
+            ("other@example.com", "Wasps456"),
+        ],
+    )
+    .edit();
+
+    let mut updated = Vec::new();
+    manager.bulk_update(|account, password| {
+        updated.push(account.to_string());
+        *password = password.to_uppercase();
+    });
+
+    updated.sort();
+    assert_eq!(updated, ["other@example.com", "test@example.com"]);
+    assert_eq!(
+        manager.get_password_ref("test@example.com"),
+        Some("BEES123")
+    );
+    assert_eq!(
+        manager.get_password_ref("other@example.com"),
+        Some("WASPS456")
+    );
+}
+
+/// Test that a master password on the blocklist is rejected while one that isn't on it is accepted.
+#[test]
+fn rejecting_common_passwords_checks_blocklist() {
+    use std::collections::HashSet;
+
+    let blocklist: HashSet<String> = ["password", "123456", "Master Password"]
+        .into_iter()
+        .map(String::from)
+        .collect();
+
+    let result = PasswordManagerBuilder::new()
+        .with_master_password("Master Password")
+        .reject_common_passwords(&blocklist);
+    assert!(matches!(result, Err(CommonPasswordError)));
+
+    let manager = PasswordManagerBuilder::new()
+        .with_master_password("Uncommon Master Password")
+        .reject_common_passwords(&blocklist)
+        .unwrap()
+        .build();
+    assert!(manager.unlock("Uncommon Master Password").is_ok());
+}
+
+/// Test that the default password is only computed and inserted for accounts that don't exist yet.
+#[test]
+fn get_password_or_insert_with_skips_default_for_present_accounts() {
+    let mut manager = PasswordManager::<Unlocked>::from_accounts(
+        "Master Password",
+        [("test@example.com", "Bees123")],
+    )
+    .edit();
+
+    let password = manager.get_password_or_insert_with("test@example.com", || {
+        panic!("the default shouldn't be computed for an existing account")
+    });
+    assert_eq!(password, "Bees123");
+
+    let mut calls = 0;
+    let password = manager.get_password_or_insert_with("other@example.com", || {
+        calls += 1;
+        "Wasps456".to_string()
+    });
+    assert_eq!(password, "Wasps456");
+    assert_eq!(calls, 1);
+    assert_eq!(
+        manager.get_password_ref("other@example.com"),
+        Some("Wasps456")
+    );
+}
+
+/// Test that the transition hook is told about every lock and unlock, in order, but not about failed unlocks.
+#[test]
+fn transition_hook_records_lock_and_unlock() {
+    use std::sync::{Arc, Mutex};
+
+    const MASTER_PASSWORD: &str = "Master Password";
+
+    let events = Arc::new(Mutex::new(Vec::new()));
+    let recorded = Arc::clone(&events);
+    let manager = PasswordManagerBuilder::new()
+        .with_master_password(MASTER_PASSWORD)
+        .on_transition(move |event| {
+            recorded
+                .lock()
+                .expect("The events shouldn't be poisoned")
+                .push(event)
+        })
+        .build();
+
+    let manager = manager
+        .unlock("Wrong Password")
+        .expect_err("Unlocking with an incorrect master password should fail");
+    let manager = manager
+        .unlock(MASTER_PASSWORD)
+        .expect("Unlocking with correct master password should work")
+        .lock();
+    let _manager = manager
+        .unlock_read_only(MASTER_PASSWORD)
+        .expect("Unlocking with correct master password should work");
+
+    assert_eq!(
+        *events.lock().expect("The events shouldn't be poisoned"),
+        [
+            TransitionEvent::Unlocked,
+            TransitionEvent::Locked,
+            TransitionEvent::Unlocked
+        ]
+    );
+}
+
+/// Test that only the accounts with weak passwords are counted and listed.
+#[test]
+fn weak_passwords_are_counted_and_listed() {
+    let manager = PasswordManager::<Unlocked>::from_accounts(
+        "Master Password",
+        [
+            ("test@example.com", "bees"),
+            ("other@example.com", "Wasps456!Wasps456!"),
+            ("another@example.com", "12345678"),
+            ("medium@example.com", "Ants7890"),
+        ],
+    );
+
+    assert_eq!(manager.weak_password_count(), 2);
+    assert_eq!(
+        manager.weak_accounts(),
+        ["another@example.com", "test@example.com"]
+    );
+}
+
+/// Test that a manager with only strong passwords has no weak accounts.
+#[test]
+fn strong_passwords_are_not_weak() {
+    let manager = PasswordManager::<Unlocked>::from_accounts(
+        "Master Password",
+        [("test@example.com", "Correct-Horse-Battery-9")],
+    );
+
+    assert_eq!(manager.weak_password_count(), 0);
+    assert!(manager.weak_accounts().is_empty());
+}
+
+/// Test that a shared manager can be read from two threads at once once it has been unlocked, and refuses reads while locked.
+#[test]
+fn shared_manager_reads_from_several_threads() {
+    use crate::shared::{NotUnlocked, SharedPasswordManager};
+
+    const MASTER_PASSWORD: &str = "Master Password";
+
+    let shared = SharedPasswordManager::new(
+        PasswordManagerBuilder::new()
+            .with_master_password(MASTER_PASSWORD)
+            .with_account("test@example.com", "Bees123")
+            .with_account("other@example.com", "Wasps456")
+            .build(),
+    );
+    assert_eq!(shared.get_password("test@example.com"), Err(NotUnlocked));
+    assert_eq!(
+        shared.unlock("Wrong Password"),
+        Err(UnlockError::WrongPassword)
+    );
+    shared
+        .unlock(MASTER_PASSWORD)
+        .expect("Unlocking with correct master password should work");
+
+    let threads: Vec<_> = [
+        ("test@example.com", "Bees123"),
+        ("other@example.com", "Wasps456"),
+    ]
+    .into_iter()
+    .map(|(account, expected)| {
+        let shared = shared.clone();
+        std::thread::spawn(move || {
+            for _ in 0..100 {
+                let password = shared
+                    .get_password(account)
+                    .expect("The shared manager should still be unlocked")
+                    .expect("Account should be present");
+                assert_eq!(password.expose(), expected);
+            }
+        })
+    })
+    .collect();
+    for thread in threads {
+        thread
+            .join()
+            .expect("Reading from another thread shouldn't panic");
+    }
+
+    shared.lock();
+    assert!(shared.is_locked());
+    assert_eq!(shared.get_password("test@example.com"), Err(NotUnlocked));
+}
+
+/// Test that a well-formed vault parses into a locked manager with every account, keeping any `=` in the passwords.
+#[test]
+fn parsing_vault_from_text_works() {
+    const MASTER_PASSWORD: &str = "Master Password";
+
+    let text =
+        format!("{MASTER_PASSWORD}\ntest@example.com=Bees123\n\nother@example.com=Wasps=456\n");
+    let manager = PasswordManager::<Locked>::try_from(text.as_str())
+        .expect("Parsing a well-formed vault should work");
+    let manager = manager
+        .unlock(MASTER_PASSWORD)
+        .expect("Unlocking with correct master password should work");
+
+    assert_eq!(manager.len(), 2);
+    assert_eq!(
+        manager.get_password_ref("test@example.com"),
+        Some("Bees123")
+    );
+    assert_eq!(
+        manager.get_password_ref("other@example.com"),
+        Some("Wasps=456")
+    );
+}
+
+/// Test that a vault without a master password line fails to parse.
+#[test]
+fn parsing_vault_without_master_password_fails() {
+    assert_eq!(
+        PasswordManager::<Locked>::try_from("").err(),
+        Some(ParseError::MissingMasterPassword)
+    );
+    assert_eq!(
+        PasswordManager::<Locked>::try_from("\ntest@example.com=Bees123").err(),
+        Some(ParseError::MissingMasterPassword)
+    );
+    assert_eq!(
+        PasswordManager::<Locked>::try_from("   \ntest@example.com=Bees123").err(),
+        Some(ParseError::MissingMasterPassword)
+    );
+}
+
+/// Test that an account line without an `=` fails to parse, reporting its line.
+#[test]
+fn parsing_vault_with_malformed_account_fails() {
+    assert_eq!(
+        PasswordManager::<Locked>::try_from(
+            "Master Password\ntest@example.com=Bees123\nother@example.com"
+        )
+        .err(),
+        Some(ParseError::MalformedAccount { line: 3 })
+    );
+}
+
+/// Test that draining a manager returns every account and leaves it empty but still locked with the same master password.
+#[test]
+fn drain_works() {
+    let mut manager = PasswordManager::from_accounts(
+        "Master Password",
+        [
+            ("test@example.com", "Bees123"),
+            ("other@example.com", "Wasps456"),
+        ],
+    )
+    .edit();
+
+    let drained = manager.drain();
+    assert_eq!(drained.len(), 2);
+    assert_eq!(drained["test@example.com"], "Bees123");
+    assert_eq!(drained["other@example.com"], "Wasps456");
+    assert!(manager.is_empty());
+
+    assert!(manager
+        .mark_saved()
+        .lock()
+        .unlock("Master Password")
+        .is_ok());
+}
+
+/// Test that a master password at least as long as the minimum length is accepted.
+#[test]
+fn min_master_length_met_works() {
+    let manager = PasswordManagerBuilder::new()
+        .with_min_master_length(8)
+        .with_master_password("Password")
+        .unwrap()
+        .with_account("test@example.com", "Bees123")
+        .build();
+
+    assert!(manager.unlock("Password").is_ok());
+}
+
+/// Test that a master password shorter than the minimum length is rejected, counting characters rather than bytes.
+#[test]
+fn min_master_length_unmet_fails() {
+    let error = PasswordManagerBuilder::new()
+        .with_min_master_length(8)
+        .with_master_password("Pässwd")
+        .err();
+
+    assert_eq!(error, Some(TooShortError { min: 8, len: 6 }));
+}
+
+/// Test that a manager taken apart with `into_inner` can be put back together with `from_accounts`.
+#[cfg(not(feature = "hashing"))]
+#[test]
+fn into_inner_round_trips() {
+    let manager = PasswordManager::from_accounts(
+        "Master Password",
+        [
+            ("test@example.com", "Bees123"),
+            ("other@example.com", "Wasps456"),
+        ],
+    );
+
+    let (master_password, accounts) = manager.into_inner();
+    assert_eq!(master_password, "Master Password");
+    assert_eq!(accounts.len(), 2);
+
+    let manager = PasswordManager::from_accounts(master_password, accounts);
+    assert_eq!(
+        manager.get_password_ref("test@example.com"),
+        Some("Bees123")
+    );
+    assert!(manager.lock().unlock("Master Password").is_ok());
+}
+
+/// Ensure rotating every password gives each account a new password of the requested length, the same ones each time for the same seed.
+#[test]
+fn rotate_all_passwords_works() {
+    const MASTER_PASSWORD: &str = "Master Password";
+
+    let opts = CharsetOptions {
+        seed: Some(3),
+        ..CharsetOptions::default()
+    };
+    let accounts = [
+        ("test@example.com", "Bees123"),
+        ("other@example.com", "Wasps456"),
+    ];
+
+    let mut manager = PasswordManager::from_accounts(MASTER_PASSWORD, accounts).edit();
+    manager.rotate_all_passwords(24, opts);
+
+    for (account, old) in accounts {
+        let new = manager
+            .get_password_ref(account)
+            .expect("Account should be present");
+        assert_ne!(new, old);
+        assert_eq!(new.len(), 24);
+        assert!(
+            manager
+                .password_history(account)
+                .expect("Account should be present")[0]
+                == *old
+        );
+    }
+    assert_ne!(
+        manager.get_password_ref("test@example.com"),
+        manager.get_password_ref("other@example.com")
+    );
+
+    let mut again = PasswordManager::from_accounts(MASTER_PASSWORD, accounts).edit();
+    again.rotate_all_passwords(24, opts);
+    assert_eq!(manager.get_passwords(), again.get_passwords());
+}
+
+/// Test that accounts can be looked up by tag, that tags aren't duplicated and that removing a tag takes the account out of the lookup.
+#[test]
+fn by_tag_and_remove_tag_work() {
+    let mut manager = PasswordManager::from_accounts(
+        "Master Password",
+        [
+            ("test@example.com", "Bees123"),
+            ("person@social.com", "Wasps456"),
+            ("me@news.biz", "Hornets789"),
+        ],
+    )
+    .edit();
+
+    manager.add_tag("test@example.com", "work");
+    manager.add_tag("me@news.biz", "work");
+    manager.add_tag("me@news.biz", "work");
+    manager.add_tag("me@news.biz", "news");
+    assert_eq!(manager.by_tag("work"), ["me@news.biz", "test@example.com"]);
+    assert_eq!(manager.by_tag("news"), ["me@news.biz"]);
+
+    assert!(manager.remove_tag("me@news.biz", "work"));
+    assert!(!manager.remove_tag("me@news.biz", "work"));
+    assert!(!manager.remove_tag("nobody@nowhere.org", "work"));
+    assert_eq!(manager.by_tag("work"), ["test@example.com"]);
+    assert_eq!(manager.get_entry("me@news.biz").unwrap().tags, ["news"]);
+}
+
+/// Test that `verify_and_run` runs the closure on the unlocked manager and hands back a locked one.
+#[test]
+fn verify_and_run_works() {
+    let manager = PasswordManagerBuilder::new()
+        .with_master_password("Master Password")
+        .with_account("test@example.com", "Bees123")
+        .build();
+
+    let (manager, password) = manager
+        .verify_and_run("Master Password", |unlocked| {
+            unlocked.get_password("test@example.com")
+        })
+        .expect("Running with the correct master password should work");
+
+    assert_eq!(password.unwrap().expose(), "Bees123");
+    assert_eq!(manager.account_count(), 1);
+}
+
+/// Test that `verify_and_run` doesn't run the closure given the wrong master password and gives back the locked manager.
+#[test]
+fn verify_and_run_with_wrong_password_fails() {
+    let manager = PasswordManagerBuilder::new()
+        .with_master_password("Master Password")
+        .with_account("test@example.com", "Bees123")
+        .build();
+
+    let manager = manager
+        .verify_and_run("Wrong Password", |_| panic!("The closure shouldn't run"))
+        .expect_err("Running with the wrong master password should fail");
+
+    assert!(manager.unlock("Master Password").is_ok());
+}
+
+/// Ensure shrinking a manager after removing most of its accounts gives back capacity while keeping the accounts that are left.
+#[test]
+fn shrink_to_fit_reclaims_capacity() {
+    let mut manager = PasswordManager::from_accounts(
+        "Master Password",
+        (0..1000).map(|i| (format!("account{i}"), "Bees123")),
+    )
+    .edit();
+    manager.retain(|account, _| account == "account0");
+    let capacity = manager.capacity();
+
+    manager.shrink_to_fit();
+
+    assert!(manager.capacity() < capacity);
+    assert_eq!(manager.get_password_ref("account0"), Some("Bees123"));
+}
+
+/// Test that looking up accounts by password finds every account sharing it and nothing else.
+#[test]
+fn accounts_with_password_works() {
+    let manager = PasswordManager::from_accounts(
+        "Master Password",
+        [
+            ("test@example.com", "Bees123"),
+            ("person@social.com", "Bees123"),
+            ("me@news.biz", "Hornets789"),
+        ],
+    );
+
+    assert_eq!(
+        manager.accounts_with_password("Bees123"),
+        ["person@social.com", "test@example.com"]
+    );
+    assert_eq!(
+        manager.accounts_with_password("Hornets789"),
+        ["me@news.biz"]
+    );
+    assert!(manager.accounts_with_password("Wasps456").is_empty());
+}
+
+/// Test that the reused password report lists shared passwords with their accounts and leaves out passwords used once.
+#[test]
+fn reused_passwords_works() {
+    let manager = PasswordManager::from_accounts(
+        "Master Password",
+        [
+            ("test@example.com", "Bees123"),
+            ("person@social.com", "Bees123"),
+            ("me@news.biz", "Hornets789"),
+        ],
+    );
+
+    let reused = manager.reused_passwords();
+
+    assert_eq!(reused.len(), 1);
+    assert_eq!(reused["Bees123"], ["person@social.com", "test@example.com"]);
+}
+
+/// Test that extending a manager inserts every account, with later passwords for the same account overwriting earlier ones.
+#[test]
+fn extending_manager_works() {
+    let mut manager =
+        PasswordManager::from_accounts("Master Password", [("test@example.com", "Bees123")]).edit();
+
+    manager.extend(vec![
+        ("person@social.com".to_string(), "Wasps456".to_string()),
+        ("test@example.com".to_string(), "Hornets789".to_string()),
+        ("person@social.com".to_string(), "Ants000".to_string()),
+    ]);
+
+    assert_eq!(manager.len(), 2);
+    assert_eq!(
+        manager.get_password_ref("test@example.com"),
+        Some("Hornets789")
+    );
+    assert_eq!(
+        manager.get_password_ref("person@social.com"),
+        Some("Ants000")
+    );
+}
+
+/// Test that the master password digest is the SHA-256 of the salt followed by the master password, so managers sharing a salt share a digest.
+#[cfg(feature = "hashing")]
+#[test]
+fn master_hash_matches_salted_digest() {
+    use sha2::{Digest, Sha256};
+
+    let manager = PasswordManagerBuilder::new()
+        .with_master_password("Master Password")
+        .build();
+    let clone = manager.clone();
+
+    let mut hasher = Sha256::new();
+    hasher.update(manager.master_salt());
+    hasher.update(b"Master Password");
+    assert_eq!(manager.master_hash(), hasher.finalize().as_slice());
+
+    assert_eq!(manager.master_salt(), clone.master_salt());
+    assert_eq!(manager.master_hash(), clone.master_hash());
+}
+
+/// Test that unlocked managers can be sorted by how many accounts they hold.
+#[test]
+fn sorting_managers_by_size_works() {
+    let mut managers: Vec<PasswordManager<Unlocked>> = vec![
+        PasswordManager::from_accounts(
+            "Master Password",
+            [
+                ("test@example.com", "Bees123"),
+                ("other@example.com", "Wasps456"),
+            ],
+        ),
+        PasswordManager::from_accounts("Master Password", Vec::<(String, String)>::new()),
+        PasswordManager::from_accounts("Master Password", [("test@example.com", "Bees123")]),
+    ];
+
+    managers.sort_by(PasswordManager::cmp_by_size);
+
+    let sizes: Vec<usize> = managers.iter().map(PasswordManager::len).collect();
+    assert_eq!(sizes, [0, 1, 2]);
+}
+
+/// Test that adding new accounts with `with_account_checked` works.
+#[test]
+fn with_account_checked_works() {
+    let manager = PasswordManagerBuilder::new()
+        .with_master_password("Master Password")
+        .with_account_checked("test@example.com", "Bees123")
+        .and_then(|builder| builder.with_account_checked("other@example.com", "Wasps456"))
+        .expect("Adding different accounts should work")
+        .build();
+
+    assert_eq!(manager.account_count(), 2);
+}
+
+/// Test that adding an account twice with `with_account_checked` fails.
+#[test]
+fn with_account_checked_rejects_duplicates() {
+    let error = PasswordManagerBuilder::new()
+        .with_master_password("Master Password")
+        .with_account("test@example.com", "Bees123")
+        .with_account_checked("test@example.com", "Wasps456")
+        .err();
+
+    assert_eq!(error, Some(DuplicateAccountError));
+}
+
+/// Ensure the master password read from a line of input has its line ending removed but keeps any other whitespace.
+#[cfg(feature = "cli")]
+#[test]
+fn reading_master_password_trims_line_ending() {
+    use crate::cli::read_master_password;
+
+    assert_eq!(
+        read_master_password(&b"Hunter2\n"[..]).expect("Reading a line should work"),
+        "Hunter2"
+    );
+    assert_eq!(
+        read_master_password(&b"Hunter2\r\nignored\n"[..]).expect("Reading a line should work"),
+        "Hunter2"
+    );
+    assert_eq!(
+        read_master_password(&b" Hunter2 "[..]).expect("Reading a line should work"),
+        " Hunter2 "
+    );
+    assert_eq!(
+        read_master_password(&b""[..])
+            .expect_err("Reading from empty input should fail")
+            .kind(),
+        std::io::ErrorKind::UnexpectedEof
+    );
+}
+
+/// Test that unlocking many managers at once splits them into the ones that share the master password and the ones that don't.
+#[test]
+fn unlock_all_works() {
+    let build = |master_password: &str, account: &str| {
+        PasswordManagerBuilder::new()
+            .with_master_password(master_password)
+            .with_account(account, "Bees123")
+            .build()
+    };
+    let managers = vec![
+        build("Master Password", "first@example.com"),
+        build("Other Password", "second@example.com"),
+        build("Master Password", "third@example.com"),
+    ];
+
+    let (unlocked, still_locked) = unlock_all(managers, "Master Password");
+
+    let accounts: Vec<Vec<&str>> = unlocked
+        .iter()
+        .map(PasswordManager::account_names_sorted)
+        .collect();
+    assert_eq!(accounts, [["first@example.com"], ["third@example.com"]]);
+    assert_eq!(still_locked.len(), 1);
+    assert!(still_locked
+        .into_iter()
+        .next()
+        .unwrap()
+        .unlock("Other Password")
+        .is_ok());
+}
+
+/// Ensure an unlocked manager reports roughly how long it has been unlocked for.
+#[test]
+fn unlocked_for_measures_time_since_unlocking() {
+    let before = Instant::now();
+    let manager = PasswordManagerBuilder::new()
+        .with_master_password("Master Password")
+        .build()
+        .unlock("Master Password")
+        .expect("Unlocking with correct master password should work");
+    assert!(manager.unlocked_since() >= before);
+
+    std::thread::sleep(Duration::from_millis(20));
+
+    let unlocked_for = manager.unlocked_for();
+    assert!(unlocked_for >= Duration::from_millis(20));
+    assert!(unlocked_for <= before.elapsed());
+}
+
+/// Test that `try_get_password` gives the password of an account that exists.
+#[test]
+fn try_get_password_works() {
+    let manager = PasswordManager::<Unlocked>::from_accounts(
+        "Master Password",
+        [("test@example.com", "Bees123")],
+    );
+
+    assert_eq!(manager.try_get_password("test@example.com"), Ok("Bees123"));
+}
+
+/// Test that `try_get_password` names the missing account in its error.
+#[test]
+fn try_get_password_missing_account_fails() {
+    let manager = PasswordManager::<Unlocked>::from_accounts(
+        "Master Password",
+        [("test@example.com", "Bees123")],
+    );
+
+    let error = manager.try_get_password("tset@example.com").unwrap_err();
+    assert_eq!(error.account, "tset@example.com");
+}
+
+/// Test that the accounts collected by a builder without a master password can be added to an unlocked manager.
+#[test]
+fn applying_accounts_from_builder_works() {
+    let mut manager = PasswordManager::<Unlocked>::from_accounts(
+        "Master Password",
+        [
+            ("test@example.com", "Bees123"),
+            ("person@social.com", "Wasps456"),
+        ],
+    )
+    .edit();
+    let accounts = PasswordManagerBuilder::new()
+        .with_account("person@social.com", "Ants000")
+        .with_account("me@news.biz", "Hornets789");
+
+    manager.apply_accounts_from(accounts);
+
+    assert_eq!(
+        manager.account_names_sorted(),
+        ["me@news.biz", "person@social.com", "test@example.com"]
+    );
+    assert_eq!(
+        manager.get_password_ref("person@social.com"),
+        Some("Ants000")
+    );
+    assert!(manager
+        .mark_saved()
+        .lock()
+        .unlock("Master Password")
+        .is_ok());
+}
+
+/// Test that a long password using every kind of character is estimated to have far more entropy than a short lowercase one.
+#[test]
+fn password_entropy_ranks_complex_passwords_higher() {
+    const MASTER_PASSWORD: &str = "Master Password";
+
+    let manager = PasswordManager::<Unlocked>::from_accounts(
+        MASTER_PASSWORD,
+        [
+            ("simple@example.com", "bees"),
+            ("complex@example.com", "Bees-123-Wasps-456"),
+        ],
+    );
+
+    // 4 characters from a pool of 26, so 4 * log2(26).
+    let simple = manager
+        .password_entropy("simple@example.com")
+        .expect("Account should be present");
+    assert!((simple - 4.0 * 26f64.log2()).abs() < 1e-9);
+    // 18 characters from a pool of 26 + 26 + 10 + 33 = 95.
+    let complex = manager
+        .password_entropy("complex@example.com")
+        .expect("Account should be present");
+    assert!((complex - 18.0 * 95f64.log2()).abs() < 1e-9);
+
+    assert!(complex > simple);
+    assert!((manager.average_entropy() - (simple + complex) / 2.0).abs() < 1e-9);
+    assert_eq!(manager.password_entropy("nobody@example.com"), None);
+}
+
+/// Test that a frozen manager can still be read from.
+#[test]
+fn reading_frozen_manager_works() {
+    let manager = PasswordManager::<Unlocked>::from_accounts(
+        "Master Password",
+        [
+            ("test@example.com", "Bees123"),
+            ("person@social.com", "Wasps456"),
+        ],
+    );
+
+    let frozen: PasswordManager<Frozen> = manager.freeze();
+
+    assert_eq!(
+        frozen.get_password("test@example.com").unwrap().expose(),
+        "Bees123"
+    );
+    let mut accounts: Vec<(&str, &str)> = frozen.iter().collect();
+    accounts.sort_unstable();
+    assert_eq!(
+        accounts,
+        [
+            ("person@social.com", "Wasps456"),
+            ("test@example.com", "Bees123")
+        ]
+    );
+    assert_eq!(frozen.to_string(), "PasswordManager(frozen, 2 accounts)");
+}
+
+/// Test that `with_account_if` only adds the account when its condition is true.
+#[test]
+fn with_account_if_works() {
+    let manager = PasswordManagerBuilder::new()
+        .with_master_password("Master Password")
+        .with_account_if(true, "test@example.com", "Bees123")
+        .with_account_if(false, "person@social.com", "Wasps456")
+        .build()
+        .unlock("Master Password")
+        .expect("Unlocking with correct master password should work");
+
+    assert_eq!(
+        manager.get_password_ref("test@example.com"),
+        Some("Bees123")
+    );
+    assert!(!manager.contains_account("person@social.com"));
+}
+
+/// Test that an account can be found by a predicate on its password.
+#[test]
+fn find_account_works() {
+    let manager = PasswordManager::<Unlocked>::from_accounts(
+        "Master Password",
+        [
+            ("test@example.com", "Bees123"),
+            ("person@social.com", "Wasps456"),
+        ],
+    );
+
+    assert_eq!(
+        manager.find_account(|_, password| password.starts_with("Wasps")),
+        Some(("person@social.com", "Wasps456"))
+    );
+    assert_eq!(
+        manager.find_account(|_, password| password.starts_with("Ants")),
+        None
+    );
+}
+
+/// Test that mapping the passwords base64 encodes every current and previous password while keeping the accounts and master password.
+#[test]
+fn map_passwords_works() {
+    fn base64(input: &str) -> String {
+        const ALPHABET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+        let mut output = String::new();
+        for chunk in input.as_bytes().chunks(3) {
+            let bytes = [
+                chunk[0],
+                *chunk.get(1).unwrap_or(&0),
+                *chunk.get(2).unwrap_or(&0),
+            ];
+            let bits = u32::from_be_bytes([0, bytes[0], bytes[1], bytes[2]]);
+            for i in 0..4 {
+                if i <= chunk.len() {
+                    output.push(ALPHABET[(bits >> (18 - 6 * i) & 63) as usize] as char);
+                } else {
+                    output.push('=');
+                }
+            }
+        }
+        output
+    }
+
+    let mut manager = PasswordManager::<Unlocked>::from_accounts(
+        "Master Password",
+        [
+            ("test@example.com", "Bees123"),
+            ("person@social.com", "Wasps456"),
+        ],
+    );
+    let mut manager = manager.insert("test@example.com", "Hornets789");
+
+    let manager = manager.map_passwords(base64);
+
+    assert_eq!(
+        manager.get_password_ref("test@example.com"),
+        Some("SG9ybmV0czc4OQ==")
+    );
+    assert_eq!(
+        manager.get_password_ref("person@social.com"),
+        Some("V2FzcHM0NTY=")
+    );
+    assert!(manager.password_history("test@example.com").unwrap()[0] == *"QmVlczEyMw==");
+    assert!(manager
+        .mark_saved()
+        .lock()
+        .unlock("Master Password")
+        .is_ok());
+}
+
+/// Test that accounts differing only by case are reported before they would be merged by case-insensitive matching.
+#[test]
+fn validating_case_collisions_reports_colliding_accounts() {
+    let error = PasswordManagerBuilder::new()
+        .with_account("Test@Example.com", "Bees123")
+        .with_account("test@example.com", "Wasps456")
+        .with_account("me@news.biz", "Hornets789")
+        .validate_no_case_collisions()
+        .err()
+        .expect("Accounts differing only by case should collide");
+
+    assert_eq!(error.collisions, [["Test@Example.com", "test@example.com"]]);
+}
+
+/// Test that accounts that differ by more than case pass validation.
+#[test]
+fn validating_case_collisions_accepts_distinct_accounts() {
+    let manager = PasswordManagerBuilder::new()
+        .with_master_password("Master Password")
+        .with_account("test@example.com", "Bees123")
+        .with_account("me@news.biz", "Hornets789")
+        .validate_no_case_collisions()
+        .expect("Distinct accounts shouldn't collide")
+        .with_case_insensitive_accounts()
+        .build();
+
+    assert_eq!(manager.account_count(), 2);
+}
+
+/// Test that swapping the passwords of two accounts swaps only their current passwords.
+#[test]
+fn swap_passwords_works() {
+    let mut manager = PasswordManager::<Unlocked>::from_accounts(
+        "Master Password",
+        [
+            ("test@example.com", "Bees123"),
+            ("person@social.com", "Wasps456"),
+        ],
+    )
+    .edit();
+
+    assert_eq!(
+        manager.swap_passwords("test@example.com", "person@social.com"),
+        Ok(())
+    );
+    assert_eq!(
+        manager.get_password_ref("test@example.com"),
+        Some("Wasps456")
+    );
+    assert_eq!(
+        manager.get_password_ref("person@social.com"),
+        Some("Bees123")
+    );
+
+    assert_eq!(
+        manager.swap_passwords("test@example.com", "test@example.com"),
+        Ok(())
+    );
+    assert_eq!(
+        manager.get_password_ref("test@example.com"),
+        Some("Wasps456")
+    );
+    assert!(manager
+        .password_history("test@example.com")
+        .unwrap()
+        .is_empty());
+}
+
+/// Test that swapping passwords fails without changing anything when either account is missing.
+#[test]
+fn swap_passwords_missing_account_fails() {
+    let mut manager = PasswordManager::<Unlocked>::from_accounts(
+        "Master Password",
+        [("test@example.com", "Bees123")],
+    )
+    .edit();
+
+    let error = manager
+        .swap_passwords("nobody@nowhere.org", "test@example.com")
+        .unwrap_err();
+    assert_eq!(error.account, "nobody@nowhere.org");
+    let error = manager
+        .swap_passwords("test@example.com", "nobody@nowhere.org")
+        .unwrap_err();
+    assert_eq!(error.account, "nobody@nowhere.org");
+    assert_eq!(
+        manager.get_password_ref("test@example.com"),
+        Some("Bees123")
+    );
+}
+
+/// Test that looking a password up by several aliases skips the missing ones and uses the first that exists.
+#[test]
+fn get_password_any_falls_back_to_later_aliases() {
+    let manager = PasswordManager::<Unlocked>::from_accounts(
+        "Master Password",
+        [
+            ("me@news.biz", "Hornets789"),
+            ("person@social.com", "Wasps456"),
+        ],
+    );
+
+    assert_eq!(
+        manager.get_password_any(["me@news.com", "me@news.biz", "person@social.com"]),
+        Some("Hornets789")
+    );
+    assert_eq!(manager.get_password_any(["me@news.com"]), None);
+}
+
+/// Ensure changes made while a manager is unlocked are encrypted when it is locked, so they are there when it is unlocked again.
+#[cfg(feature = "encryption")]
+#[test]
+fn locking_encrypts_edited_accounts() {
+    const MASTER_PASSWORD: &str = "Master Password";
+
+    let mut manager = PasswordManagerBuilder::new()
+        .with_master_password(MASTER_PASSWORD)
+        .with_account("test@example.com", "Bees123")
+        .with_account("me@news.biz", "Hornets789")
+        .build()
+        .unlock(MASTER_PASSWORD)
+        .expect("Unlocking with correct master password should work")
+        .insert("test@example.com", "Wasps456");
+    manager.insert("person@social.com", "Ants000");
+    manager.remove_account("me@news.biz");
+    let file = tempfile::NamedTempFile::new().expect("Creating a temporary file should work");
+
+    let manager = manager
+        .save_to_file(file.path())
+        .expect("Saving a dirty manager should work")
+        .lock();
+    assert_eq!(manager.account_count(), 2);
+
+    let manager = manager
+        .unlock(MASTER_PASSWORD)
+        .expect("Unlocking with correct master password should work");
+    assert_eq!(
+        manager.get_password_ref("test@example.com"),
+        Some("Wasps456")
+    );
+    assert_eq!(
+        manager.get_password_ref("person@social.com"),
+        Some("Ants000")
+    );
+    assert!(!manager.contains_account("me@news.biz"));
+}
+
+/// Test that a manager keeping its accounts in a `BTreeMap` can be locked, unlocked, looked up in and inserted into directly, and lists its
+/// accounts sorted no matter what order they were inserted in.
+#[test]
+fn btree_map_store_lists_accounts_in_order() {
+    const MASTER_PASSWORD: &str = "Master Password";
+
+    let manager = PasswordManager::<Unlocked>::from_accounts(
+        MASTER_PASSWORD,
+        [
+            ("zebra@example.com", "Bees123"),
+            ("mango@example.com", "Ants789"),
+        ],
+    )
+    .with_store::<BTreeMap<String, AccountEntry>>()
+    .lock()
+    .unlock(MASTER_PASSWORD)
+    .expect("Unlocking with correct master password should work");
+    assert_eq!(
+        manager.get_password("mango@example.com"),
+        Some("Ants789".into())
+    );
+
+    let manager = manager.insert("apple@example.com", "Wasps456");
+    assert_eq!(manager.len(), 3);
+    assert_eq!(
+        manager.iter().collect::<Vec<_>>(),
+        [
+            ("apple@example.com", "Wasps456"),
+            ("mango@example.com", "Ants789"),
+            ("zebra@example.com", "Bees123")
+        ]
+    );
+}